@@ -0,0 +1,249 @@
+use crate::{
+    bot,
+    game_assets::GameAssets,
+    lanes::{Lane, LaneViews},
+    progress::Progress,
+    settings::Settings,
+    stats::RunStats,
+    theme::{Theme, ThemeText},
+    ButtonAction, UiFontSize, WindowSizeDiagonalWeighted, PLAY_DIAGONAL_WEIGHTED, PLAY_HEIGHT,
+};
+use bevy::prelude::*;
+
+const TOAST_TIME: f32 = 3.0;
+// font size relative to the weighted play area diagonal
+const TOAST_FONT_SIZE: f32 = 0.04;
+
+pub struct Achievement {
+    // stored in the progress file, don't change it
+    id: &'static str,
+    name: &'static str,
+    description: &'static str,
+    condition: fn(&RunStats, &Progress) -> bool,
+}
+
+pub static ACHIEVEMENTS: [Achievement; 7] = [
+    Achievement {
+        id: "first_bite",
+        name: "First bite",
+        description: "Eat a food",
+        condition: |run, _| run.food_eaten >= 1,
+    },
+    Achievement {
+        id: "gatekeeper",
+        name: "Gatekeeper",
+        description: "Pass 10 gates without eating",
+        condition: |run, _| run.gates_since_food >= 10,
+    },
+    Achievement {
+        id: "glutton",
+        name: "Glutton",
+        description: "Eat 20 food in one run",
+        condition: |run, _| run.food_eaten >= 20,
+    },
+    Achievement {
+        id: "half_century",
+        name: "Half century",
+        description: "Reach a score of 50",
+        condition: |run, _| run.score >= 50,
+    },
+    Achievement {
+        id: "speedrunner",
+        name: "Speedrunner",
+        description: "Score 50 in under 60 s",
+        condition: |run, _| run.score >= 50 && run.time < 60.0,
+    },
+    Achievement {
+        id: "marathon",
+        name: "Marathon",
+        description: "Survive for 3 minutes",
+        condition: |run, _| run.time >= 180.0,
+    },
+    Achievement {
+        id: "veteran",
+        name: "Veteran",
+        description: "Crash 25 times",
+        // the current run is added to the lifetime stats after the deathscreen shows up
+        condition: |run, progress| progress.deaths() + run.death_cause.is_some() as u32 >= 25,
+    },
+];
+
+impl Achievement {
+    pub fn is_unlocked(&self, progress: &Progress) -> bool {
+        progress.achievements.iter().any(|id| id == self.id)
+    }
+}
+
+// component: a message at the top of the view that fades out
+pub struct Toast(Timer);
+
+// unlocks achievements, the run stats are updated before
+pub fn check_achievements(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    run_stats: Res<RunStats>,
+    mut progress: ResMut<Progress>,
+    game_assets: Res<GameAssets>,
+    theme: Res<Theme>,
+) {
+    if bot::skill_of(&settings, 0).is_some() {
+        return;
+    }
+    let mut unlocked = Vec::new();
+    for achievement in ACHIEVEMENTS.iter() {
+        if !achievement.is_unlocked(&progress) && (achievement.condition)(&run_stats, &progress) {
+            unlocked.push(achievement);
+        }
+    }
+    for achievement in unlocked {
+        progress.achievements.push(achievement.id.to_string());
+        progress.save();
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    format!("Achievement unlocked: {}", achievement.name),
+                    TextStyle {
+                        font: game_assets.font_medium.clone(),
+                        font_size: TOAST_FONT_SIZE * PLAY_DIAGONAL_WEIGHTED,
+                        color: theme.text,
+                    },
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        vertical: VerticalAlign::Center,
+                    },
+                ),
+                ..Default::default()
+            })
+            .insert(Toast(Timer::from_seconds(TOAST_TIME, false)))
+            .insert(ThemeText::Primary);
+    }
+}
+
+// keeps the toasts below each other at the top of the view
+pub fn update_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    theme: Res<Theme>,
+    lane_views: Res<LaneViews>,
+    mut query: Query<(Entity, &mut Transform, &mut Text, &mut Toast)>,
+) {
+    // achievements are only unlocked in single player, which has only the first lane
+    let camera_y = lane_views.get(&Lane(0)).camera_y;
+    let line_height = 1.5 * TOAST_FONT_SIZE * PLAY_DIAGONAL_WEIGHTED;
+    let mut index = 0;
+    for (entity, mut tf, mut text, mut toast) in query.iter_mut() {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        tf.translation = Vec3::new(
+            0.0,
+            camera_y + 0.4 * PLAY_HEIGHT - index as f32 * line_height,
+            60.0,
+        );
+        index += 1;
+        // fade out during the last second
+        let mut color = theme.text;
+        color.set_a((toast.0.percent_left() * TOAST_TIME).min(1.0));
+        for section in text.sections.iter_mut() {
+            section.style.color = color;
+        }
+    }
+}
+
+pub fn spawn_achievements_screen(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    theme: Res<Theme>,
+    progress: Res<Progress>,
+    window_size_diag: Res<WindowSizeDiagonalWeighted>,
+) {
+    let text = |value: String, font: Handle<Font>, font_size: f32, color: Color| TextBundle {
+        text: Text::with_section(
+            value,
+            TextStyle {
+                font,
+                font_size: window_size_diag.0 * font_size,
+                color,
+            },
+            TextAlignment::default(),
+        ),
+        ..Default::default()
+    };
+    commands.spawn_bundle(UiCameraBundle::default());
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::SpaceAround,
+                ..Default::default()
+            },
+            material: game_assets.deathscreen_material.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            let unlocked_count = ACHIEVEMENTS
+                .iter()
+                .filter(|achievement| achievement.is_unlocked(&progress))
+                .count();
+            parent
+                .spawn_bundle(text(
+                    format!("Achievements {}/{}", unlocked_count, ACHIEVEMENTS.len()),
+                    game_assets.font_bold.clone(),
+                    0.08,
+                    theme.text,
+                ))
+                .insert(UiFontSize(0.08))
+                .insert(ThemeText::Primary);
+            for achievement in ACHIEVEMENTS.iter() {
+                let (mark, theme_text) = if achievement.is_unlocked(&progress) {
+                    ("[x]", ThemeText::Primary)
+                } else {
+                    ("[ ]", ThemeText::Secondary)
+                };
+                parent
+                    .spawn_bundle(text(
+                        format!(
+                            "{} {} - {}",
+                            mark, achievement.name, achievement.description
+                        ),
+                        game_assets.font_medium.clone(),
+                        0.035,
+                        theme_text.color(&theme),
+                    ))
+                    .insert(UiFontSize(0.035))
+                    .insert(theme_text);
+            }
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        padding: Rect::all(Val::Px(8.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    material: game_assets.button_normal_material.clone(),
+                    ..Default::default()
+                })
+                .insert(ButtonAction::Menu)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(text(
+                            "Back".to_string(),
+                            game_assets.font_medium.clone(),
+                            0.06,
+                            theme.text_secondary,
+                        ))
+                        .insert(UiFontSize(0.06))
+                        .insert(ThemeText::Secondary);
+                });
+        });
+}
+
+pub fn exit_achievements_screen(mut commands: Commands, query: Query<Entity>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,330 @@
+use crate::{
+    game_assets::{self, GameAssets},
+    settings::Settings,
+    Food, Gate, Player, Velocity, Wall, PLAY_DIAGONAL_WEIGHTED, VEL_CLIPPING,
+};
+use bevy::prelude::*;
+
+// a push that changes the speed by this much (relative to the weighted play area diagonal per
+// second) squashes the player completely
+const SQUASH_FULL_PUSH: f32 = 1.0;
+const SQUASH_TIME: f32 = 0.25;
+// how much the player is deformed at most
+const SQUASH_AMOUNT: f32 = 0.35;
+
+// a sprite sheet in assets/art, every row is one animation
+pub struct ArtSheet {
+    path: &'static str,
+    tile_size: f32,
+    columns: usize,
+    rows: usize,
+    fps: f32,
+}
+
+// player: idle animation in the first row, squash animation in the second row
+static PLAYER_SHEET: ArtSheet = ArtSheet {
+    path: "art/player.png",
+    tile_size: 32.0,
+    columns: 4,
+    rows: 2,
+    fps: 6.0,
+};
+static FOOD_SHEET: ArtSheet = ArtSheet {
+    path: "art/food.png",
+    tile_size: 32.0,
+    columns: 4,
+    rows: 1,
+    fps: 8.0,
+};
+// walls: 9-slice with the corners in the corner tiles, the edges are stretched along the wall and
+// the middle in both directions
+static WALL_SHEET: ArtSheet = ArtSheet {
+    path: "art/wall.png",
+    tile_size: 32.0,
+    columns: 3,
+    rows: 3,
+    fps: 0.0,
+};
+static GATE_SHEET: ArtSheet = ArtSheet {
+    path: "art/gate.png",
+    tile_size: 32.0,
+    columns: 4,
+    rows: 1,
+    fps: 8.0,
+};
+
+// the sprite sheets that were found, entities without art keep their flat colors
+#[derive(Default)]
+pub struct Art {
+    pub player: Option<Handle<TextureAtlas>>,
+    pub food: Option<Handle<TextureAtlas>>,
+    pub wall: Option<Handle<TextureAtlas>>,
+    pub gate: Option<Handle<TextureAtlas>>,
+}
+
+impl Art {
+    // only existing files are loaded, so missing art is not an error; the returned textures
+    // have to be waited for in the loading state
+    pub fn load(
+        asset_server: &AssetServer,
+        atlases: &mut Assets<TextureAtlas>,
+    ) -> (Art, Vec<(&'static str, HandleUntyped)>) {
+        let mut files = Vec::new();
+        let mut load = |sheet: &ArtSheet| {
+            if !game_assets::asset_path(sheet.path).exists() {
+                return None;
+            }
+            let texture = asset_server.load_untyped(sheet.path);
+            files.push((sheet.path, texture.clone()));
+            Some(atlases.add(TextureAtlas::from_grid(
+                texture.typed(),
+                Vec2::splat(sheet.tile_size),
+                sheet.columns,
+                sheet.rows,
+            )))
+        };
+        let art = Art {
+            player: load(&PLAYER_SHEET),
+            food: load(&FOOD_SHEET),
+            wall: load(&WALL_SHEET),
+            gate: load(&GATE_SHEET),
+        };
+        (art, files)
+    }
+}
+
+// component: draws the art of another entity, it is not a child because the sprite culling
+// only looks at the local transform
+pub struct ArtFollower {
+    target: Entity,
+    offset: Vec2,
+    // scale of the tile so it has the size of the target (or the slice)
+    scale: Vec2,
+}
+
+// component
+pub struct SpriteAnimation {
+    sheet: &'static ArtSheet,
+    row: usize,
+    timer: f32,
+}
+
+// component: the player gets squashed in the direction of a push
+#[derive(Default)]
+pub struct Squash {
+    direction: Vec2,
+    // 1.0 right after the push, 0.0 when it is over
+    amount: f32,
+    last_velocity: Vec2,
+}
+
+fn spawn_follower(
+    commands: &mut Commands,
+    atlas: &Handle<TextureAtlas>,
+    index: u32,
+    follower: ArtFollower,
+    z: f32,
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: atlas.clone(),
+            sprite: TextureAtlasSprite::new(index),
+            transform: Transform::from_xyz(0.0, 0.0, z + 0.01),
+            ..Default::default()
+        })
+        .insert(follower)
+        .id()
+}
+
+// tile index, offset from the middle and size of every slice of a 3x3 sheet stretched to the
+// size, the corners stay square and are as big as a third of the shorter side
+fn nine_slices(size: Vec2) -> [(u32, Vec2, Vec2); 9] {
+    let corner = size.x.min(size.y) / 3.0;
+    // offset and length of the start, middle and end of one side, the rows go from top to bottom
+    let columns = [
+        (-(size.x - corner) / 2.0, corner),
+        (0.0, size.x - 2.0 * corner),
+        ((size.x - corner) / 2.0, corner),
+    ];
+    let rows = [
+        ((size.y - corner) / 2.0, corner),
+        (0.0, size.y - 2.0 * corner),
+        (-(size.y - corner) / 2.0, corner),
+    ];
+    let mut slices = [(0, Vec2::ZERO, Vec2::ZERO); 9];
+    for (row, (y, height)) in rows.iter().enumerate() {
+        for (column, (x, width)) in columns.iter().enumerate() {
+            let index = row * 3 + column;
+            slices[index] = (index as u32, Vec2::new(*x, *y), Vec2::new(*width, *height));
+        }
+    }
+    slices
+}
+
+// gives new entities their art and hides their flat sprite
+pub fn attach_art(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    mut query: Query<
+        (
+            Entity,
+            &Transform,
+            &Sprite,
+            &mut Visible,
+            Option<&Player>,
+            Option<&Food>,
+            Option<&Wall>,
+            Option<&Gate>,
+        ),
+        Or<(Added<Player>, Added<Food>, Added<Wall>, Added<Gate>)>,
+    >,
+) {
+    let art = &game_assets.art;
+    for (entity, tf, sprite, mut visible, player, food, wall, gate) in query.iter_mut() {
+        let z = tf.translation.z;
+        let size = sprite.size;
+        if let (Some(atlas), Some(_)) = (&art.wall, wall) {
+            for (index, offset, slice_size) in nine_slices(size).iter() {
+                let follower = ArtFollower {
+                    target: entity,
+                    offset: *offset,
+                    scale: *slice_size / WALL_SHEET.tile_size,
+                };
+                spawn_follower(&mut commands, atlas, *index, follower, z);
+            }
+            visible.is_visible = false;
+            continue;
+        }
+        let animated = if player.is_some() {
+            art.player.as_ref().map(|atlas| (atlas, &PLAYER_SHEET))
+        } else if food.is_some() {
+            art.food.as_ref().map(|atlas| (atlas, &FOOD_SHEET))
+        } else if gate.is_some() {
+            art.gate.as_ref().map(|atlas| (atlas, &GATE_SHEET))
+        } else {
+            None
+        };
+        if let Some((atlas, sheet)) = animated {
+            let follower = ArtFollower {
+                target: entity,
+                offset: Vec2::ZERO,
+                scale: size / sheet.tile_size,
+            };
+            let follower = spawn_follower(&mut commands, atlas, 0, follower, z);
+            commands.entity(follower).insert(SpriteAnimation {
+                sheet,
+                row: 0,
+                timer: 0.0,
+            });
+            visible.is_visible = false;
+        }
+    }
+}
+
+// followers of despawned entities are despawned too
+pub fn follow_targets(
+    mut commands: Commands,
+    target_query: Query<&Transform, Without<ArtFollower>>,
+    mut follower_query: Query<(Entity, &mut Transform, &ArtFollower)>,
+) {
+    for (entity, mut tf, follower) in follower_query.iter_mut() {
+        if let Ok(target_tf) = target_query.get(follower.target) {
+            let offset =
+                target_tf.rotation * (follower.offset * target_tf.scale.truncate()).extend(0.0);
+            tf.translation.x = target_tf.translation.x + offset.x;
+            tf.translation.y = target_tf.translation.y + offset.y;
+            tf.rotation = target_tf.rotation;
+            tf.scale = (follower.scale * target_tf.scale.truncate()).extend(1.0);
+        } else {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn animate_sprites(
+    time: Res<Time>,
+    mut query: Query<(&mut TextureAtlasSprite, &mut SpriteAnimation)>,
+) {
+    for (mut sprite, mut animation) in query.iter_mut() {
+        let sheet = animation.sheet;
+        animation.timer += time.delta_seconds();
+        let frame = (animation.timer * sheet.fps) as usize % sheet.columns;
+        sprite.index = (animation.row * sheet.columns + frame) as u32;
+    }
+}
+
+// squashes the players when they get pushed and switches their art to the squash animation
+pub fn squash_player(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut commands: Commands,
+    mut player_query: Query<(Entity, &mut Transform, &Velocity, Option<&mut Squash>), With<Player>>,
+    mut animation_query: Query<(&ArtFollower, &mut SpriteAnimation)>,
+) {
+    for (entity, mut tf, velocity, squash) in player_query.iter_mut() {
+        let mut squash = match squash {
+            Some(squash) => squash,
+            None => {
+                commands.entity(entity).insert(Squash::default());
+                continue;
+            }
+        };
+        // friction only slows the player down, a push is a change of the velocity that friction
+        // doesn't explain
+        let velocity = velocity.0.truncate();
+        let expected_velocity = squash.last_velocity * VEL_CLIPPING.powf(time.delta_seconds());
+        let push = velocity - expected_velocity;
+        squash.last_velocity = velocity;
+        let strength = push.length() / (SQUASH_FULL_PUSH * PLAY_DIAGONAL_WEIGHTED);
+        if velocity.length() > 0.0 && strength > 0.05 {
+            squash.direction = push.normalize();
+            squash.amount = strength.min(1.0);
+            for (follower, mut animation) in animation_query.iter_mut() {
+                if follower.target == entity && animation.sheet.rows > 1 {
+                    animation.row = 1;
+                    animation.timer = 0.0;
+                }
+            }
+        }
+        squash.amount = (squash.amount - time.delta_seconds() / SQUASH_TIME).max(0.0);
+        if squash.amount == 0.0 {
+            for (follower, mut animation) in animation_query.iter_mut() {
+                if follower.target == entity {
+                    animation.row = 0;
+                }
+            }
+        }
+        if settings.reduced_motion {
+            tf.scale = Vec3::ONE;
+            continue;
+        }
+        // shorter in the direction of the push and wider across it
+        let deform = SQUASH_AMOUNT * squash.amount;
+        let along = Vec2::new(squash.direction.x.abs(), squash.direction.y.abs());
+        let across = Vec2::new(along.y, along.x);
+        let scale = Vec2::ONE - deform * along + deform * across;
+        tf.scale = scale.extend(1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nine_slices_cover_the_wall() {
+        let size = Vec2::new(90.0, 12.0);
+        let slices = nine_slices(size);
+        let area: f32 = slices.iter().map(|(_, _, size)| size.x * size.y).sum();
+        assert!((area - size.x * size.y).abs() < 1e-3);
+        // corners are square and stay in the corners
+        for index in [0, 2, 6, 8].iter() {
+            let (_, offset, slice_size) = slices[*index];
+            assert_eq!(slice_size, Vec2::splat(4.0));
+            assert_eq!(offset.abs() + slice_size / 2.0, size / 2.0);
+        }
+        // the top left tile is in the top left corner
+        assert!(slices[0].1.x < 0.0 && slices[0].1.y > 0.0);
+        assert_eq!(slices[4], (4, Vec2::ZERO, Vec2::new(82.0, 4.0)));
+    }
+}
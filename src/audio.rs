@@ -0,0 +1,103 @@
+use crate::{settings::Settings, PLAY_WIDTH};
+use bevy::prelude::*;
+use bevy_kira_audio::{Audio, AudioChannel, AudioSource};
+use serde::{Deserialize, Serialize};
+
+// volumes from 0.0 to 1.0, music and sound effects are multiplied with the master volume
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+    // M toggles it
+    pub muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            master: 1.0,
+            music: 0.7,
+            sfx: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl AudioSettings {
+    fn volume(&self, volume: f32) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            (self.master * volume).clamp(0.0, 1.0)
+        }
+    }
+
+    pub fn music_volume(&self) -> f32 {
+        self.volume(self.music)
+    }
+
+    pub fn sfx_volume(&self) -> f32 {
+        self.volume(self.sfx)
+    }
+}
+
+// sounds that play at the same time need their own channel to be panned differently
+const PANNED_CHANNELS: usize = 8;
+// panning at the edges of the play area, 0.0 is only the left speaker
+const MAX_PANNING: f32 = 0.4;
+
+// Resource: the channels for sound effects, the music has its own channels (see music.rs)
+pub struct SfxChannels {
+    // ui sounds, centered
+    pub effects: AudioChannel,
+    // sounds from the play area, used in turns
+    panned: Vec<AudioChannel>,
+    next_panned: usize,
+}
+
+impl Default for SfxChannels {
+    fn default() -> Self {
+        SfxChannels {
+            effects: AudioChannel::new("sfx".to_string()),
+            panned: (0..PANNED_CHANNELS)
+                .map(|i| AudioChannel::new(format!("sfx_panned_{}", i)))
+                .collect(),
+            next_panned: 0,
+        }
+    }
+}
+
+impl SfxChannels {
+    fn all(&self) -> impl Iterator<Item = &AudioChannel> {
+        std::iter::once(&self.effects).chain(self.panned.iter())
+    }
+
+    // plays a sound panned by its x position in the play area, the pitch is the playback rate
+    pub fn play_at(&mut self, audio: &Audio, source: Handle<AudioSource>, x: f32, pitch: f32) {
+        let channel = &self.panned[self.next_panned];
+        self.next_panned = (self.next_panned + 1) % self.panned.len();
+        let panning = 0.5 + 2.0 * MAX_PANNING * (x / PLAY_WIDTH).clamp(-0.5, 0.5);
+        audio.set_panning_in_channel(panning, channel);
+        audio.set_playback_rate_in_channel(pitch, channel);
+        audio.play_in_channel(source, channel);
+    }
+}
+
+// M mutes and unmutes everything
+pub fn toggle_mute(keyboard: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+    if keyboard.just_pressed(KeyCode::M) {
+        settings.audio.muted = !settings.audio.muted;
+        settings.save();
+    }
+}
+
+// the music volume is applied while fading, see music::update_music
+pub fn apply_sfx_volume(audio: Res<Audio>, settings: Res<Settings>, channels: Res<SfxChannels>) {
+    if settings.is_changed() {
+        for channel in channels.all() {
+            audio.set_volume_in_channel(settings.audio.sfx_volume(), channel);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{
+    game_assets::GameAssets,
+    lanes::{Lane, LaneViews},
+    theme::Theme,
+    PLAY_DIAGONAL_WEIGHTED, PLAY_WIDTH,
+};
+use bevy::prelude::*;
+use rand::Rng;
+
+pub struct StarLayer {
+    // how much of the camera movement the layer follows on the screen, far layers move slower
+    rate: f32,
+    // relative to the weighted play area diagonal
+    size: f32,
+    // stars at a star density of 1.0
+    count: f32,
+    // multiplied with the alpha of the theme color
+    pub alpha: f32,
+}
+
+// far to near
+pub const STAR_LAYERS: [StarLayer; 3] = [
+    StarLayer {
+        rate: 0.15,
+        size: 0.003,
+        count: 40.0,
+        alpha: 0.35,
+    },
+    StarLayer {
+        rate: 0.35,
+        size: 0.0045,
+        count: 24.0,
+        alpha: 0.6,
+    },
+    StarLayer {
+        rate: 0.6,
+        size: 0.006,
+        count: 12.0,
+        alpha: 0.9,
+    },
+];
+
+// component
+pub struct Star {
+    rate: f32,
+    // position in the repeating band of the layer (0.0 - 1.0)
+    offset: f32,
+}
+
+// the stars are spawned whenever the theme changes because the density depends on it.
+// every lane has its own stars
+pub fn update_starfield(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    lane_views: Res<LaneViews>,
+    game_assets: Res<GameAssets>,
+    mut query: Query<(Entity, &mut Transform, &Star, &Lane)>,
+) {
+    // the band is a bit bigger than the view so stars never pop in at the edges
+    let band_height = |lane: &Lane| 2.0 * lane_views.get(lane).half_height * 1.1;
+    if theme.is_changed() || query.iter_mut().next().is_none() {
+        for (entity, _, _, _) in query.iter_mut() {
+            commands.entity(entity).despawn();
+        }
+        for index in 0..lane_views.0.len() {
+            let lane = Lane(index);
+            let camera_y = lane_views.get(&lane).camera_y;
+            spawn_stars(
+                &mut commands,
+                &theme,
+                &game_assets,
+                lane,
+                camera_y,
+                band_height(&lane),
+            );
+        }
+        return;
+    }
+    for (_, mut tf, star, lane) in query.iter_mut() {
+        tf.translation.y = star.y(lane_views.get(lane).camera_y, band_height(lane));
+    }
+}
+
+impl Star {
+    // the layer repeats every band height and moves with a part of the camera movement
+    fn y(&self, camera_y: f32, band_height: f32) -> f32 {
+        let relative_y = (self.offset - camera_y * self.rate / band_height).rem_euclid(1.0);
+        camera_y + (relative_y - 0.5) * band_height
+    }
+}
+
+fn spawn_stars(
+    commands: &mut Commands,
+    theme: &Theme,
+    game_assets: &GameAssets,
+    lane: Lane,
+    camera_y: f32,
+    band_height: f32,
+) {
+    let materials = &game_assets.star_materials;
+    let mut rng = rand::thread_rng();
+    for (index, (layer, material)) in STAR_LAYERS.iter().zip(materials.iter()).enumerate() {
+        let count = (layer.count * theme.star_density).round() as usize;
+        let size = layer.size * PLAY_DIAGONAL_WEIGHTED;
+        for _ in 0..count {
+            let star = Star {
+                rate: layer.rate,
+                offset: rng.gen_range(0.0..1.0),
+            };
+            commands
+                .spawn_bundle(SpriteBundle {
+                    material: material.clone(),
+                    sprite: Sprite::new(Vec2::new(size, size)),
+                    // behind everything else, but still in front of the camera's far plane
+                    transform: Transform::from_xyz(
+                        lane.x() + rng.gen_range(-PLAY_WIDTH / 2.0..PLAY_WIDTH / 2.0),
+                        star.y(camera_y, band_height),
+                        -0.09 + 0.01 * index as f32,
+                    ),
+                    visible: Visible {
+                        is_visible: true,
+                        is_transparent: true,
+                    },
+                    ..Default::default()
+                })
+                .insert(star)
+                .insert(lane);
+        }
+    }
+}
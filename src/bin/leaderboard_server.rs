@@ -0,0 +1,145 @@
+// reference leaderboard server, run it with `cargo run --bin leaderboard_server [address]`.
+// it verifies every submitted run and keeps the scores in save/leaderboard_server.json
+use std::{
+    fs,
+    net::{TcpListener, TcpStream},
+    path::Path,
+    time::Duration,
+};
+
+#[allow(dead_code)]
+#[path = "../leaderboard_protocol.rs"]
+mod leaderboard_protocol;
+
+use leaderboard_protocol::{Entry, ErrorResponse, Submission, SubmitResult, TOP_ENTRIES};
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+const SCORES_FILE: &str = "save/leaderboard_server.json";
+// longer names are cut
+const MAX_NAME_LENGTH: usize = 16;
+// a client that stops sending is dropped after this
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn load_scores() -> Vec<Entry> {
+    match fs::read_to_string(SCORES_FILE) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+            eprintln!("Could not parse {}: {}", SCORES_FILE, err);
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn save_scores(scores: &[Entry]) {
+    let result = serde_json::to_string_pretty(scores)
+        .map_err(|err| err.to_string())
+        .and_then(|content| {
+            if let Some(dir) = Path::new(SCORES_FILE).parent() {
+                fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+            }
+            fs::write(SCORES_FILE, content).map_err(|err| err.to_string())
+        });
+    if let Err(err) = result {
+        eprintln!("Could not write {}: {}", SCORES_FILE, err);
+    }
+}
+
+fn top(scores: &[Entry]) -> Vec<Entry> {
+    scores.iter().take(TOP_ENTRIES).cloned().collect()
+}
+
+// verifies the run and adds it, returns its rank
+fn submit(scores: &mut Vec<Entry>, body: &str) -> Result<SubmitResult, String> {
+    let submission: Submission = serde_json::from_str(body).map_err(|err| err.to_string())?;
+    leaderboard_protocol::verify(&submission)?;
+    let name: String = submission
+        .name
+        .trim()
+        .chars()
+        .take(MAX_NAME_LENGTH)
+        .collect();
+    let entry = Entry {
+        name: if name.is_empty() {
+            "Anonymous".to_string()
+        } else {
+            name
+        },
+        score: submission.score,
+        seed: submission.seed,
+    };
+    // behind every entry with the same score, they were first
+    let index = scores
+        .iter()
+        .position(|other| other.score < entry.score)
+        .unwrap_or(scores.len());
+    println!(
+        "{} scored {} on seed {}",
+        entry.name, entry.score, entry.seed
+    );
+    scores.insert(index, entry);
+    save_scores(scores);
+    Ok(SubmitResult {
+        rank: index + 1,
+        top: top(scores),
+    })
+}
+
+// returns the status line and the body
+fn handle_request(scores: &mut Vec<Entry>, stream: &mut TcpStream) -> (&'static str, String) {
+    let request = match leaderboard_protocol::read_message(stream) {
+        Ok(request) => request,
+        Err(err) => return error("400 Bad Request", err.to_string()),
+    };
+    let method = request.start_line.split_whitespace().next().unwrap_or("");
+    let path = request.target().split('?').next().unwrap_or("");
+    let result = match (method, path) {
+        ("GET", "/scores") => serde_json::to_string(&top(scores)),
+        ("POST", "/scores") => match submit(scores, &request.body) {
+            Ok(result) => serde_json::to_string(&result),
+            Err(err) => {
+                println!("Rejected a run: {}", err);
+                return error("400 Bad Request", err);
+            }
+        },
+        _ => return error("404 Not Found", format!("{} {} not found", method, path)),
+    };
+    match result {
+        Ok(body) => ("200 OK", body),
+        Err(err) => error("500 Internal Server Error", err.to_string()),
+    }
+}
+
+fn error(status: &'static str, error: String) -> (&'static str, String) {
+    let body = serde_json::to_string(&ErrorResponse { error }).unwrap_or_default();
+    (status, body)
+}
+
+fn main() {
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+    let listener = TcpListener::bind(&address).unwrap_or_else(|err| {
+        eprintln!("Could not listen on {}: {}", address, err);
+        std::process::exit(1);
+    });
+    println!("Leaderboard server listening on http://{}", address);
+    let mut scores = load_scores();
+    // one request at a time is plenty for testing
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("Connection failed: {}", err);
+                continue;
+            }
+        };
+        if let Err(err) = stream.set_read_timeout(Some(READ_TIMEOUT)) {
+            eprintln!("Could not set the timeout: {}", err);
+        }
+        let (status, body) = handle_request(&mut scores, &mut stream);
+        let head = format!("HTTP/1.1 {}", status);
+        if let Err(err) = leaderboard_protocol::write_message(&mut stream, &head, &body) {
+            eprintln!("Could not send the response: {}", err);
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use crate::{
+    controls::{Controller, DragGesture},
+    lanes::{GameMode, Lane, LaneViews},
+    settings::Settings,
+    AppState, Crashed, Food, Gate, GatePasses, Player, Score, Velocity, Wall, PLAYER_SPEED,
+    PLAY_DIAGONAL_WEIGHTED, VEL_CLIPPING,
+};
+use bevy::{app::AppExit, prelude::*};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// how far above or below a gate the bot aims, relative to the weighted play diagonal
+const GATE_CLEARANCE: f32 = 0.06;
+// the bot stays at least this far above the bottom of the view
+const BOTTOM_MARGIN: f32 = 0.15;
+// food further away than this is ignored
+const FOOD_RANGE: f32 = 0.3;
+// no push is needed if the player comes to rest this close to the target
+const TARGET_TOLERANCE: f32 = 0.03;
+// walls are avoided with this much room on every side
+const WALL_MARGIN: f32 = 0.01;
+// the longest drag of the bot
+const MAX_DRAG: f32 = 0.5;
+// runs of `--autopilot` without a count
+const DEFAULT_TRIAL_RUNS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BotSkill {
+    Easy,
+    Normal,
+    Hard,
+}
+
+struct SkillParams {
+    // seconds between letting go and aiming again
+    reaction: f32,
+    // seconds the drag is held before it is released
+    aim_time: f32,
+    // random error of the direction in radians and of the length relative to it
+    aim_error: f32,
+    eats_food: bool,
+}
+
+impl BotSkill {
+    fn params(&self) -> SkillParams {
+        match self {
+            BotSkill::Easy => SkillParams {
+                reaction: 0.6,
+                aim_time: 0.3,
+                aim_error: 0.25,
+                eats_food: false,
+            },
+            BotSkill::Normal => SkillParams {
+                reaction: 0.35,
+                aim_time: 0.2,
+                aim_error: 0.1,
+                eats_food: true,
+            },
+            BotSkill::Hard => SkillParams {
+                reaction: 0.15,
+                aim_time: 0.1,
+                aim_error: 0.03,
+                eats_food: true,
+            },
+        }
+    }
+}
+
+// the skill of the bot that plays the player, None if a human plays it.
+// runs with a bot as the first player don't count for the stats, achievements, ghost and leaderboard
+pub fn skill_of(settings: &Settings, player_index: usize) -> Option<BotSkill> {
+    if player_index == 0 && settings.autopilot_override.is_some() {
+        return settings.autopilot_override;
+    }
+    settings.autopilot.get(player_index).copied().flatten()
+}
+
+// Resource: `--autopilot <easy|normal|hard> [runs]` lets the bot play single runs one after another
+// and prints their scores, for balance testing
+pub struct AutopilotTrial {
+    pub skill: Option<BotSkill>,
+    runs: usize,
+    scores: Vec<i32>,
+}
+
+impl AutopilotTrial {
+    pub fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let mut trial = AutopilotTrial {
+            skill: None,
+            runs: DEFAULT_TRIAL_RUNS,
+            scores: Vec::new(),
+        };
+        if let Some(index) = args.iter().position(|arg| arg == "--autopilot") {
+            trial.skill = args.get(index + 1).and_then(|skill| parse_skill(skill));
+            if trial.skill.is_none() {
+                eprintln!("Usage: --autopilot <easy|normal|hard> [runs]");
+            }
+            if let Some(runs) = args.get(index + 2).and_then(|runs| runs.parse().ok()) {
+                trial.runs = runs;
+            }
+        }
+        trial
+    }
+}
+
+fn parse_skill(name: &str) -> Option<BotSkill> {
+    match name.to_lowercase().as_str() {
+        "easy" => Some(BotSkill::Easy),
+        "normal" => Some(BotSkill::Normal),
+        "hard" => Some(BotSkill::Hard),
+        _ => None,
+    }
+}
+
+// starts the next run from the menu and the deathscreen, quits once every run is done
+pub fn run_trial(
+    mut trial: ResMut<AutopilotTrial>,
+    mut mode: ResMut<GameMode>,
+    mut app_state: ResMut<State<AppState>>,
+    mut app_exit: EventWriter<AppExit>,
+    player_query: Query<&Score, With<Player>>,
+) {
+    let skill = match trial.skill {
+        Some(skill) => skill,
+        None => return,
+    };
+    // only the deathscreen has a player
+    if let Ok(score) = player_query.single() {
+        trial.scores.push(score.0);
+        println!(
+            "Run {}/{}: score {}",
+            trial.scores.len(),
+            trial.runs,
+            score.0
+        );
+    }
+    if trial.scores.len() < trial.runs {
+        *mode = GameMode::Single;
+        let _ = app_state.set(AppState::Playing);
+        return;
+    }
+    let total: i32 = trial.scores.iter().sum();
+    println!(
+        "Autopilot {:?}: {} runs, average {:.1}, best {}, worst {}",
+        skill,
+        trial.scores.len(),
+        total as f32 / trial.scores.len().max(1) as f32,
+        trial.scores.iter().max().unwrap_or(&0),
+        trial.scores.iter().min().unwrap_or(&0)
+    );
+    app_exit.send(AppExit);
+}
+
+// component: the timers and the aim of a player that is played by the bot
+#[derive(Default)]
+pub struct Autopilot {
+    cooldown: f32,
+    // seconds until the drag is released while aiming
+    aim_left: Option<f32>,
+    aim: Option<Vec2>,
+    release: bool,
+}
+
+impl Autopilot {
+    // used by gesture_on_player instead of the input, returns the drag vector when the bot lets go
+    pub fn update(&mut self, gesture: &mut DragGesture) -> Option<Vec2> {
+        if self.release {
+            self.release = false;
+            gesture.is_dragging = false;
+            return self.aim.take();
+        }
+        if let Some(aim) = self.aim {
+            gesture.vector = aim;
+            gesture.is_dragging = true;
+        }
+        None
+    }
+}
+
+// the velocity decays by VEL_CLIPPING every second, so a player moving at v travels v / decay()
+fn decay() -> f32 {
+    -VEL_CLIPPING.ln()
+}
+
+// if the line from a to b crosses the rectangle
+fn segment_hits_rect(from: Vec2, to: Vec2, min: Vec2, max: Vec2) -> bool {
+    let direction = to - from;
+    let (mut t_min, mut t_max) = (0.0f32, 1.0f32);
+    for (d, o, lo, hi) in [
+        (direction.x, from.x, min.x, max.x),
+        (direction.y, from.y, min.y, max.y),
+    ]
+    .iter()
+    {
+        if d.abs() < f32::EPSILON {
+            if o < lo || o > hi {
+                return false;
+            }
+        } else {
+            let (t1, t2) = ((lo - o) / d, (hi - o) / d);
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+            if t_min > t_max {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// aims for food and gates while avoiding walls, and lets go after a moment like a player would.
+// the push itself happens in gesture_on_player
+pub fn drive_bots(
+    time: Res<Time>,
+    lane_views: Res<LaneViews>,
+    mut bot_query: Query<
+        (
+            Entity,
+            &Transform,
+            &Velocity,
+            &Sprite,
+            &Lane,
+            &Controller,
+            &mut Autopilot,
+        ),
+        (With<Player>, Without<Crashed>),
+    >,
+    wall_query: Query<(&Transform, &Sprite, &Lane), With<Wall>>,
+    gate_query: Query<(&Transform, &Sprite, &Lane, &GatePasses), With<Gate>>,
+    food_query: Query<(&Transform, &Lane), With<Food>>,
+) {
+    let mut rng = rand::thread_rng();
+    for (player, tf, velocity, sprite, lane, controller, mut autopilot) in bot_query.iter_mut() {
+        let params = match controller {
+            Controller::Bot(skill) => skill.params(),
+            _ => continue,
+        };
+        if let Some(aim_left) = autopilot.aim_left {
+            let aim_left = aim_left - time.delta_seconds();
+            if aim_left <= 0.0 {
+                // let go, the push happens this frame
+                autopilot.release = true;
+                autopilot.aim_left = None;
+                autopilot.cooldown = params.reaction;
+            } else {
+                autopilot.aim_left = Some(aim_left);
+            }
+            continue;
+        }
+        autopilot.cooldown -= time.delta_seconds();
+        if autopilot.cooldown > 0.0 {
+            continue;
+        }
+
+        let position = tf.translation.truncate();
+        let velocity = velocity.0.truncate();
+        let half_size = sprite.size / 2.0;
+        let view = lane_views.get(lane);
+        let bottom = view.camera_y - view.half_height + BOTTOM_MARGIN * PLAY_DIAGONAL_WEIGHTED;
+        let margin = Vec2::splat(WALL_MARGIN * PLAY_DIAGONAL_WEIGHTED);
+        let walls: Vec<(Vec2, Vec2)> = wall_query
+            .iter()
+            .filter(|(_, _, wall_lane)| *wall_lane == lane)
+            .map(|(wall_tf, wall_sprite, _)| {
+                let extent = wall_sprite.size / 2.0 + half_size + margin;
+                let center = wall_tf.translation.truncate();
+                (center - extent, center + extent)
+            })
+            .collect();
+        let clear = |from: Vec2, to: Vec2| {
+            walls
+                .iter()
+                .all(|(min, max)| !segment_hits_rect(from, to, *min, *max))
+        };
+
+        // the lowest gate that is still ahead
+        let gate = gate_query
+            .iter()
+            .filter(|(gate_tf, _, gate_lane, passes)| {
+                *gate_lane == lane
+                    && !passes.0.contains(&player)
+                    && gate_tf.translation.y > position.y - half_size.y
+            })
+            .min_by(|(a, _, _, _), (b, _, _, _)| {
+                a.translation.y.partial_cmp(&b.translation.y).unwrap()
+            })
+            .map(|(gate_tf, gate_sprite, _, _)| (gate_tf.translation.truncate(), gate_sprite.size));
+        let clearance = GATE_CLEARANCE * PLAY_DIAGONAL_WEIGHTED;
+        let mut targets = Vec::new();
+        if params.eats_food {
+            // food on this side of the next gate
+            let limit = gate.map_or(f32::INFINITY, |(gate_position, _)| gate_position.y);
+            let food = food_query
+                .iter()
+                .filter(|(_, food_lane)| *food_lane == lane)
+                .map(|(food_tf, _)| food_tf.translation.truncate())
+                .filter(|food| {
+                    food.y < limit
+                        && food.y > bottom
+                        && food.distance(position) < FOOD_RANGE * PLAY_DIAGONAL_WEIGHTED
+                })
+                .min_by(|a, b| {
+                    a.distance(position)
+                        .partial_cmp(&b.distance(position))
+                        .unwrap()
+                });
+            targets.extend(food);
+        }
+        match gate {
+            Some((gate_position, gate_size)) => {
+                // through the gate if the player is below it, otherwise below it first
+                if (position.x - gate_position.x).abs() < gate_size.x / 2.0 - half_size.x {
+                    targets.push(gate_position + Vec2::new(0.0, clearance));
+                } else {
+                    targets.push(gate_position - Vec2::new(0.0, clearance));
+                }
+            }
+            None => targets.push(Vec2::new(lane.x(), view.camera_y + view.half_height / 3.0)),
+        }
+        // sideways out of the way if nothing else is reachable
+        targets.push(Vec2::new(lane.x(), position.y.max(bottom)));
+        let target = match targets.into_iter().find(|target| clear(position, *target)) {
+            Some(target) => target,
+            None => continue,
+        };
+
+        // where the player comes to rest without a push
+        let rest = position + velocity / decay();
+        let on_track = rest.distance(target) < TARGET_TOLERANCE * PLAY_DIAGONAL_WEIGHTED
+            && rest.y > bottom
+            && clear(position, rest);
+        if on_track {
+            continue;
+        }
+        let mut impulse = (target - position) * decay() - velocity;
+        // aiming isn't perfect
+        let angle = rng.gen_range(-params.aim_error..=params.aim_error);
+        let length = 1.0 + rng.gen_range(-params.aim_error..=params.aim_error);
+        impulse = Vec2::new(
+            impulse.x * angle.cos() - impulse.y * angle.sin(),
+            impulse.x * angle.sin() + impulse.y * angle.cos(),
+        ) * length;
+        // like pulling a slingshot, away from the push
+        let drag = -impulse / PLAYER_SPEED;
+        let max_drag = MAX_DRAG * PLAY_DIAGONAL_WEIGHTED;
+        autopilot.aim = Some(if drag.length() > max_drag {
+            drag.normalize() * max_drag
+        } else {
+            drag
+        });
+        autopilot.aim_left = Some(params.aim_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_through_a_rect_hit_it() {
+        let (min, max) = (Vec2::new(-1.0, -1.0), Vec2::new(1.0, 1.0));
+        assert!(segment_hits_rect(
+            Vec2::new(-5.0, 0.0),
+            Vec2::new(5.0, 0.0),
+            min,
+            max
+        ));
+        assert!(segment_hits_rect(
+            Vec2::new(0.0, -5.0),
+            Vec2::new(0.0, 0.0),
+            min,
+            max
+        ));
+        assert!(segment_hits_rect(
+            Vec2::new(-3.0, -3.0),
+            Vec2::new(3.0, 3.0),
+            min,
+            max
+        ));
+        // starting inside
+        assert!(segment_hits_rect(Vec2::ZERO, Vec2::new(0.5, 0.5), min, max));
+    }
+
+    #[test]
+    fn segments_next_to_a_rect_miss_it() {
+        let (min, max) = (Vec2::new(-1.0, -1.0), Vec2::new(1.0, 1.0));
+        // ends before the rect
+        assert!(!segment_hits_rect(
+            Vec2::new(-5.0, 0.0),
+            Vec2::new(-2.0, 0.0),
+            min,
+            max
+        ));
+        // parallel and above it
+        assert!(!segment_hits_rect(
+            Vec2::new(-5.0, 2.0),
+            Vec2::new(5.0, 2.0),
+            min,
+            max
+        ));
+        // diagonal past the corner
+        assert!(!segment_hits_rect(
+            Vec2::new(0.0, 3.0),
+            Vec2::new(3.0, 0.0),
+            min,
+            max
+        ));
+    }
+
+    #[test]
+    fn the_override_plays_the_first_player() {
+        let mut settings = Settings::default();
+        assert_eq!(skill_of(&settings, 0), None);
+        settings.autopilot = vec![None, Some(BotSkill::Easy)];
+        assert_eq!(skill_of(&settings, 0), None);
+        assert_eq!(skill_of(&settings, 1), Some(BotSkill::Easy));
+        assert_eq!(skill_of(&settings, 2), None);
+        settings.autopilot_override = Some(BotSkill::Hard);
+        assert_eq!(skill_of(&settings, 0), Some(BotSkill::Hard));
+        assert_eq!(skill_of(&settings, 1), Some(BotSkill::Easy));
+    }
+
+    #[test]
+    fn skills_are_parsed_case_insensitively() {
+        assert_eq!(parse_skill("Hard"), Some(BotSkill::Hard));
+        assert_eq!(parse_skill("easy"), Some(BotSkill::Easy));
+        assert_eq!(parse_skill("expert"), None);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{
+    game_assets::GameAssets,
+    lanes::{self, GameMode, Lane, LaneView, LaneViews},
+    settings::Settings,
+    Crashed, Player, Score, WindowSize, PLAY_DIAGONAL_WEIGHTED, PLAY_HEIGHT, PLAY_WIDTH,
+};
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const CAMERA_SPEED: f32 = 128.0;
+// the camera starts to speed up once the player is above this relative height (-1 bottom, 1 top)
+const LOOK_AHEAD_START: f32 = 0.25;
+// trauma lost per second
+const TRAUMA_DECAY: f32 = 1.5;
+// shake offset relative to the weighted window diagonal
+const MAX_SHAKE_OFFSET: f32 = 0.02;
+// shake rotation in radians
+const MAX_SHAKE_ANGLE: f32 = 0.05;
+// speedup over the base speed at which the camera is zoomed out completely
+const ZOOM_FULL_SPEEDUP: f32 = 1.5;
+// big enough to cover the window next to the play area for any aspect ratio
+const LETTERBOX_SIZE: f32 = 10.0 * PLAY_DIAGONAL_WEIGHTED;
+
+// every effect can be disabled by setting it to 0
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    // how fast the camera speed follows its target speed
+    pub smoothing: f32,
+    // extra speed when the player is at the top of the view, 1.0 doubles the speed
+    pub look_ahead: f32,
+    pub shake: f32,
+    // zoom out at high speed, 0.1 shows 10% more
+    pub zoom_out: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            smoothing: 4.0,
+            look_ahead: 1.5,
+            shake: 1.0,
+            zoom_out: 0.1,
+        }
+    }
+}
+
+// component: one per lane camera
+pub struct CameraState {
+    pub speed: f32,
+    // speed over the base speed, 0.0 at the start of a run
+    pub speedup: f32,
+    // 0.0 - 1.0, the shake grows with the square of it
+    pub trauma: f32,
+    pub zoom: f32,
+    // sideways shake, added to the x of the lane
+    shake_offset: f32,
+}
+
+impl Default for CameraState {
+    fn default() -> Self {
+        CameraState {
+            speed: 0.0,
+            speedup: 0.0,
+            trauma: 0.0,
+            zoom: 1.0,
+            shake_offset: 0.0,
+        }
+    }
+}
+
+impl CameraState {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+
+    // the visible height grows when the camera zooms out
+    pub fn half_view_height(&self) -> f32 {
+        PLAY_HEIGHT / 2.0 * self.zoom
+    }
+}
+
+// component: covers the window outside of the play area, the value points away from it
+pub struct LetterboxBar(Vec2);
+
+// scale of the camera so the whole play area fits into the window
+pub fn view_scale(window_width: f32, window_height: f32) -> f32 {
+    (PLAY_WIDTH / window_width).max(PLAY_HEIGHT / window_height)
+}
+
+pub fn spawn_letterbox(commands: &mut Commands, game_assets: &GameAssets, lane: Lane) {
+    for direction in [Vec2::X, -Vec2::X, Vec2::Y, -Vec2::Y].iter() {
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: game_assets.letterbox_material.clone(),
+                sprite: Sprite::new(Vec2::new(LETTERBOX_SIZE, LETTERBOX_SIZE)),
+                transform: Transform::from_xyz(0.0, 0.0, 50.0),
+                ..Default::default()
+            })
+            .insert(LetterboxBar(*direction))
+            .insert(lane);
+    }
+}
+
+// moves the cameras upwards all the time, the camera of a lane without live players stops
+pub fn camera_movement(
+    mut query_set: QuerySet<(
+        Query<(&mut Transform, &mut CameraState, &Lane), With<OrthographicProjection>>,
+        Query<(&Transform, &Score, &Lane), (With<Player>, Without<Crashed>)>,
+    )>,
+    mut lane_views: ResMut<LaneViews>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    // the best score and the highest player of every lane
+    let mut lane_players: HashMap<usize, (i32, f32)> = HashMap::new();
+    for (tf, score, lane) in query_set.q1().iter() {
+        let entry = lane_players
+            .entry(lane.0)
+            .or_insert((score.0, tf.translation.y));
+        entry.0 = entry.0.max(score.0);
+        entry.1 = entry.1.max(tf.translation.y);
+    }
+    let base_speed = CAMERA_SPEED * PLAY_DIAGONAL_WEIGHTED / 720.0;
+    let delta = time.delta_seconds();
+    for (mut transform, mut camera_state, lane) in query_set.q0_mut().iter_mut() {
+        let half_view_height = camera_state.half_view_height();
+        let mut target_speed = 0.0;
+        if let Some(&(score, player_y)) = lane_players.get(&lane.0) {
+            target_speed = base_speed * (1.0 + 0.025 * score as f32);
+            // speed up when the player is near the top so it never gets pinned against the ceiling
+            let relative_y = (player_y - transform.translation.y) / half_view_height;
+            if relative_y > LOOK_AHEAD_START {
+                target_speed *= 1.0
+                    + settings.camera.look_ahead * (relative_y - LOOK_AHEAD_START)
+                        / (1.0 - LOOK_AHEAD_START);
+            }
+        }
+        if settings.camera.smoothing > 0.0 {
+            camera_state.speed += (target_speed - camera_state.speed)
+                * (1.0 - (-settings.camera.smoothing * delta).exp());
+        } else {
+            camera_state.speed = target_speed;
+        }
+        transform.translation.y += camera_state.speed * delta;
+
+        let speedup = camera_state.speed / base_speed - 1.0;
+        camera_state.speedup = speedup.max(0.0);
+        let zoom_out = if settings.reduced_motion {
+            0.0
+        } else {
+            settings.camera.zoom_out
+        };
+        camera_state.zoom = 1.0 + zoom_out * (speedup / ZOOM_FULL_SPEEDUP).clamp(0.0, 1.0);
+
+        if lane_views.0.len() <= lane.0 {
+            lane_views.0.resize(lane.0 + 1, LaneView::default());
+        }
+        lane_views.0[lane.0] = LaneView {
+            camera_y: transform.translation.y,
+            half_height: camera_state.half_view_height(),
+        };
+    }
+}
+
+// fits the play area of every lane into its part of the window, zoomed out by the camera state
+pub fn fit_view(
+    window_size: Res<WindowSize>,
+    mode: Res<GameMode>,
+    mut query: Query<(&mut Transform, &CameraState, &Lane), With<OrthographicProjection>>,
+) {
+    for (mut tf, camera_state, lane) in query.iter_mut() {
+        let scale = lanes::lane_scale(&window_size, *mode, camera_state.zoom);
+        tf.scale = Vec3::new(scale, scale, 1.0);
+        tf.translation.x =
+            lanes::camera_x(lane, *mode, &window_size, scale) + camera_state.shake_offset;
+    }
+}
+
+// keeps the letterbox around the play area of every lane.
+// in split screen the bars only cover the part of the window of their lane
+pub fn update_letterbox(
+    window_size: Res<WindowSize>,
+    mode: Res<GameMode>,
+    lane_views: Res<LaneViews>,
+    camera_query: Query<(&CameraState, &Lane)>,
+    mut bar_query: Query<(&mut Transform, &mut Sprite, &LetterboxBar, &Lane)>,
+) {
+    let mut cameras = HashMap::new();
+    for (camera_state, lane) in camera_query.iter() {
+        cameras.insert(lane.0, (camera_state.zoom, camera_state.shake_offset));
+    }
+    for (mut tf, mut sprite, bar, lane) in bar_query.iter_mut() {
+        let view = lane_views.get(lane);
+        let half_play_size = Vec2::new(PLAY_WIDTH / 2.0, view.half_height);
+        let (zoom, shake_offset) = cameras.get(&lane.0).copied().unwrap_or((1.0, 0.0));
+        let mut size = Vec2::splat(LETTERBOX_SIZE);
+        if *mode == GameMode::SplitScreen {
+            let scale = lanes::lane_scale(&window_size, *mode, zoom);
+            let half_lane_width = window_size.width * scale / 4.0;
+            // the bar towards the other lane ends in the middle of the window
+            let is_inner = (lane.0 == 0) == (bar.0.x > 0.0);
+            if bar.0.y != 0.0 {
+                size.x = 2.0 * half_lane_width;
+            } else if is_inner {
+                size.x = (half_lane_width - PLAY_WIDTH / 2.0).max(0.0);
+            }
+        }
+        sprite.size = size;
+        let offset = bar.0 * (half_play_size + size / 2.0);
+        // the bars shake with the camera, so the play area doesn't show past them
+        tf.translation.x = lane.x() + offset.x + shake_offset;
+        tf.translation.y = view.camera_y + offset.y;
+    }
+}
+
+// the shake only moves the cameras sideways and rotates them, so the scrolling is not affected
+pub fn camera_shake(
+    settings: Res<Settings>,
+    mut query: Query<(&mut Transform, &mut CameraState), With<OrthographicProjection>>,
+    time: Res<Time>,
+) {
+    let mut rng = rand::thread_rng();
+    for (mut tf, mut camera_state) in query.iter_mut() {
+        camera_state.trauma = (camera_state.trauma - TRAUMA_DECAY * time.delta_seconds()).max(0.0);
+        let mut shake = settings.camera.shake * camera_state.trauma * camera_state.trauma;
+        if settings.reduced_motion {
+            shake = 0.0;
+        }
+        camera_state.shake_offset =
+            shake * MAX_SHAKE_OFFSET * PLAY_DIAGONAL_WEIGHTED * rng.gen_range(-1.0..1.0);
+        tf.rotation = Quat::from_rotation_z(shake * MAX_SHAKE_ANGLE * rng.gen_range(-1.0..1.0));
+    }
+}
@@ -0,0 +1,297 @@
+use crate::{
+    settings::Settings,
+    skins::PlayerShape,
+    theme::{self, Theme},
+};
+use bevy::{
+    prelude::*,
+    render::texture::{Extent3d, TextureDimension, TextureFormat},
+};
+use serde::{Deserialize, Serialize};
+
+// size of the generated shape textures in pixels
+const SHAPE_TEXTURE_SIZE: u32 = 32;
+// number of dashes of a gate
+const GATE_DASHES: u32 = 8;
+// gates stay see-through, so the player can tell them apart from the walls
+const GATE_ALPHA: f32 = 0.6;
+
+// palettes are based on the Okabe-Ito colors, they replace the colors that carry gameplay information
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorblindMode {
+    Off,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+impl Default for ColorblindMode {
+    fn default() -> Self {
+        ColorblindMode::Off
+    }
+}
+
+impl ColorblindMode {
+    const ALL: [ColorblindMode; 4] = [
+        ColorblindMode::Off,
+        ColorblindMode::Deuteranopia,
+        ColorblindMode::Protanopia,
+        ColorblindMode::Tritanopia,
+    ];
+
+    fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|mode| *mode == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn apply(self, theme: &mut Theme) {
+        // player, food candidates, gate candidates, the candidates that stand out the most in
+        // the theme are used
+        let (player, foods, gates): (&str, &[&str], &[&str]) = match self {
+            ColorblindMode::Off => return,
+            ColorblindMode::Deuteranopia => (
+                "E69F00",
+                &["56B4E9", "0072B2", "F0E442"],
+                &["F0E442", "56B4E9", "0072B2", "FFFFFF", "000000"],
+            ),
+            ColorblindMode::Protanopia => (
+                "F0E442",
+                &["56B4E9", "0072B2", "E69F00"],
+                &["E69F00", "56B4E9", "0072B2", "FFFFFF", "000000"],
+            ),
+            ColorblindMode::Tritanopia => (
+                "D55E00",
+                &["F0E442", "56B4E9", "CC79A7"],
+                &["CC79A7", "009E73", "56B4E9", "FFFFFF", "000000"],
+            ),
+        };
+        theme.player = hex(player);
+        theme.food = most_visible(
+            foods.iter().map(|food| hex(food)),
+            &[theme.background, theme.player],
+            |food| food,
+        );
+        let background = theme.background;
+        let food = theme.food;
+        // a gate is drawn over the background, so its blended color has to stand out
+        let mut gate = most_visible(
+            gates
+                .iter()
+                .map(|gate| hex(gate))
+                .filter(|gate| *gate != food),
+            &[background, theme.wall, food],
+            |gate| blend(gate, background, GATE_ALPHA),
+        );
+        gate.set_a(GATE_ALPHA);
+        theme.gate = gate;
+        // the shapes make sure nothing depends on color alone
+        theme.shapes = true;
+    }
+}
+
+fn hex(value: &str) -> Color {
+    Color::hex(value).unwrap()
+}
+
+// relative luminance from 0.0 (black) to 1.0 (white)
+fn luminance(color: Color) -> f32 {
+    let [red, green, blue, _] = color.as_linear_rgba_f32();
+    0.2126 * red + 0.7152 * green + 0.0722 * blue
+}
+
+// contrast ratio from 1.0 (same brightness) to 21.0 (black on white)
+fn contrast(first: Color, second: Color) -> f32 {
+    let (first, second) = (luminance(first), luminance(second));
+    (first.max(second) + 0.05) / (first.min(second) + 0.05)
+}
+
+// the candidate whose lowest contrast to the other colors is the highest, seen_as gives the
+// color a candidate ends up on screen
+fn most_visible(
+    candidates: impl Iterator<Item = Color>,
+    others: &[Color],
+    seen_as: impl Fn(Color) -> Color,
+) -> Color {
+    let lowest_contrast = |candidate: Color| {
+        others
+            .iter()
+            .map(|other| contrast(seen_as(candidate), *other))
+            .fold(f32::INFINITY, f32::min)
+    };
+    candidates
+        .max_by(|a, b| {
+            lowest_contrast(*a)
+                .partial_cmp(&lowest_contrast(*b))
+                .unwrap()
+        })
+        .unwrap()
+}
+
+// the opaque color of a see-through color over the background
+fn blend(color: Color, background: Color, alpha: f32) -> Color {
+    Color::rgb(
+        color.r() * alpha + background.r() * (1.0 - alpha),
+        color.g() * alpha + background.g() * (1.0 - alpha),
+        color.b() * alpha + background.b() * (1.0 - alpha),
+    )
+}
+
+// textures that give food, gates and the player their own shape, a plain texture is used when the
+// shapes are off
+pub struct ShapeTextures {
+    pub plain: Handle<Texture>,
+    pub food: Handle<Texture>,
+    pub gate: Handle<Texture>,
+    pub round: Handle<Texture>,
+    pub diamond: Handle<Texture>,
+}
+
+impl ShapeTextures {
+    pub fn new(textures: &mut Assets<Texture>) -> Self {
+        ShapeTextures {
+            plain: textures.add(rgba_texture(1, 1, |_, _| 255)),
+            food: textures.add(food_texture()),
+            gate: textures.add(rgba_texture(GATE_DASHES, 1, |x, _| {
+                if x % 2 == 0 {
+                    255
+                } else {
+                    64
+                }
+            })),
+            round: textures.add(shape_texture(|position| position.length())),
+            diamond: textures.add(shape_texture(|position| {
+                position.x.abs() + position.y.abs()
+            })),
+        }
+    }
+
+    pub fn player(&self, shape: PlayerShape) -> &Handle<Texture> {
+        match shape {
+            PlayerShape::Square => &self.plain,
+            PlayerShape::Round => &self.round,
+            PlayerShape::Diamond => &self.diamond,
+        }
+    }
+}
+
+// white texture, the alpha of every pixel comes from the function
+fn rgba_texture(width: u32, height: u32, alpha: impl Fn(u32, u32) -> u8) -> Texture {
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            data.extend_from_slice(&[255, 255, 255, alpha(x, y)]);
+        }
+    }
+    Texture::new(
+        Extent3d::new(width, height, 1),
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+// white shape with smooth edges, the function returns 1.0 at the edge of the shape for positions
+// from -1.0 to 1.0
+fn shape_texture(edge_distance: impl Fn(Vec2) -> f32) -> Texture {
+    let half_size = SHAPE_TEXTURE_SIZE as f32 / 2.0;
+    rgba_texture(SHAPE_TEXTURE_SIZE, SHAPE_TEXTURE_SIZE, |x, y| {
+        let position = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) / half_size - Vec2::ONE;
+        let alpha = ((1.0 - edge_distance(position)) * half_size).clamp(0.0, 1.0);
+        (alpha * 255.0) as u8
+    })
+}
+
+// a round pickup with a darker ring, so it doesn't look like a piece of a wall
+fn food_texture() -> Texture {
+    let radius = SHAPE_TEXTURE_SIZE as f32 / 2.0;
+    let mut data = Vec::with_capacity((SHAPE_TEXTURE_SIZE * SHAPE_TEXTURE_SIZE * 4) as usize);
+    for y in 0..SHAPE_TEXTURE_SIZE {
+        for x in 0..SHAPE_TEXTURE_SIZE {
+            let distance =
+                Vec2::new(x as f32 + 0.5 - radius, y as f32 + 0.5 - radius).length() / radius;
+            let alpha = ((1.0 - distance) * radius).clamp(0.0, 1.0);
+            let brightness = if (0.55..0.75).contains(&distance) {
+                140
+            } else {
+                255
+            };
+            data.extend_from_slice(&[brightness, brightness, brightness, (alpha * 255.0) as u8]);
+        }
+    }
+    Texture::new(
+        Extent3d::new(SHAPE_TEXTURE_SIZE, SHAPE_TEXTURE_SIZE, 1),
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+// C switches to the next colorblind mode
+pub fn switch_colorblind_mode(
+    keyboard: Res<Input<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut theme: ResMut<Theme>,
+) {
+    if keyboard.just_pressed(KeyCode::C) {
+        settings.colorblind = settings.colorblind.next();
+        settings.save();
+        *theme = theme::load_theme(&settings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a gate is easy to see when its contrast ratio is at least this
+    const MIN_GATE_CONTRAST: f32 = 1.5;
+
+    fn themes() -> Vec<(&'static str, Theme)> {
+        vec![
+            ("default", include_str!("../assets/themes/default.ron")),
+            ("dark", include_str!("../assets/themes/dark.ron")),
+            (
+                "high_contrast",
+                include_str!("../assets/themes/high_contrast.ron"),
+            ),
+        ]
+        .into_iter()
+        .map(|(name, content)| (name, ron::de::from_str(content).unwrap()))
+        .collect()
+    }
+
+    #[test]
+    fn off_keeps_the_theme() {
+        let mut theme = Theme::default();
+        ColorblindMode::Off.apply(&mut theme);
+        assert_eq!(theme.gate, Theme::default().gate);
+        assert!(!theme.shapes);
+    }
+
+    #[test]
+    fn gates_stand_out_in_every_theme() {
+        for mode in ColorblindMode::ALL.iter().skip(1) {
+            for (name, mut theme) in themes() {
+                mode.apply(&mut theme);
+                let gate = blend(theme.gate, theme.background, theme.gate.a());
+                for (other, color) in [
+                    ("background", theme.background),
+                    ("wall", theme.wall),
+                    ("food", theme.food),
+                ]
+                .iter()
+                {
+                    assert!(
+                        contrast(gate, *color) >= MIN_GATE_CONTRAST,
+                        "{:?} gate blends into the {} of {}",
+                        mode,
+                        other,
+                        name
+                    );
+                }
+                assert!(contrast(theme.food, theme.background) >= MIN_GATE_CONTRAST);
+                assert!(theme.shapes);
+            }
+        }
+    }
+}
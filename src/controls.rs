@@ -0,0 +1,165 @@
+use crate::{bot::BotSkill, PLAY_DIAGONAL_WEIGHTED};
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+// stick deflection below this counts as released
+const STICK_DEADZONE: f32 = 0.3;
+// how fast holding a direction charges the push, relative to the weighted play diagonal per second
+const CHARGE_SPEED: f32 = 0.6;
+// the longest drag a held direction can charge
+const MAX_CHARGE: f32 = 0.35;
+
+// component: how the player is steered
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Controller {
+    // drag anywhere and release, or charge with the left stick of the gamepad
+    Mouse(Gamepad),
+    // hold the arrow keys or the left stick of the gamepad to charge a push, release to push
+    Keys(Gamepad),
+    // played by the autopilot in bot.rs
+    Bot(BotSkill),
+}
+
+// component: the drag vector in world units points away from where the player will be pushed
+pub struct DragGesture {
+    pub start_pos: Vec2,
+    pub is_dragging: bool,
+    pub vector: Vec2,
+}
+
+impl Default for DragGesture {
+    fn default() -> Self {
+        DragGesture {
+            start_pos: Vec2::ZERO,
+            is_dragging: false,
+            vector: Vec2::ZERO,
+        }
+    }
+}
+
+// everything a controller can read
+#[derive(SystemParam)]
+pub struct PlayerInput<'a> {
+    mouse_buttons: Res<'a, Input<MouseButton>>,
+    keyboard: Res<'a, Input<KeyCode>>,
+    gamepad_axes: Res<'a, Axis<GamepadAxis>>,
+    windows: Res<'a, Windows>,
+    time: Res<'a, Time>,
+}
+
+// every player has its own gamepad, the first player the first one
+pub fn gamepad_of(player_index: usize) -> Gamepad {
+    Gamepad(player_index)
+}
+
+impl<'a> PlayerInput<'a> {
+    // the direction the arrow keys point to, zero if nothing is held
+    fn keys_direction(&self) -> Vec2 {
+        let mut direction = Vec2::ZERO;
+        for (key, dir) in [
+            (KeyCode::Up, Vec2::Y),
+            (KeyCode::Down, -Vec2::Y),
+            (KeyCode::Left, -Vec2::X),
+            (KeyCode::Right, Vec2::X),
+        ]
+        .iter()
+        {
+            if self.keyboard.pressed(*key) {
+                direction += *dir;
+            }
+        }
+        direction.normalize_or_zero()
+    }
+
+    // the direction the left stick points to, zero inside of the deadzone
+    fn stick_direction(&self, gamepad: Gamepad) -> Vec2 {
+        let stick = Vec2::new(
+            self.gamepad_axes
+                .get(GamepadAxis(gamepad, GamepadAxisType::LeftStickX))
+                .unwrap_or(0.0),
+            self.gamepad_axes
+                .get(GamepadAxis(gamepad, GamepadAxisType::LeftStickY))
+                .unwrap_or(0.0),
+        );
+        if stick.length() > STICK_DEADZONE {
+            stick.normalize_or_zero()
+        } else {
+            Vec2::ZERO
+        }
+    }
+
+    // holding a direction charges the push, letting go returns the drag vector
+    fn charge(&self, direction: Vec2, gesture: &mut DragGesture) -> Option<Vec2> {
+        if direction != Vec2::ZERO {
+            let max_length = MAX_CHARGE * PLAY_DIAGONAL_WEIGHTED;
+            // a new charge starts at zero, not at the last drag
+            let charged = if gesture.is_dragging {
+                gesture.vector.length()
+            } else {
+                0.0
+            };
+            let length = (charged
+                + CHARGE_SPEED * PLAY_DIAGONAL_WEIGHTED * self.time.delta_seconds())
+            .min(max_length);
+            // like pulling a slingshot, away from the push
+            gesture.vector = -direction * length;
+            gesture.is_dragging = true;
+            None
+        } else if gesture.is_dragging {
+            gesture.is_dragging = false;
+            let vector = gesture.vector;
+            gesture.vector = Vec2::ZERO;
+            Some(vector)
+        } else {
+            None
+        }
+    }
+
+    // updates the gesture and returns the drag vector when it was released.
+    // scale converts pixels into world units
+    pub fn update(
+        &self,
+        controller: Controller,
+        gesture: &mut DragGesture,
+        scale: f32,
+    ) -> Option<Vec2> {
+        match controller {
+            Controller::Mouse(gamepad)
+                if !self.mouse_buttons.pressed(MouseButton::Left)
+                    && !self.mouse_buttons.just_released(MouseButton::Left) =>
+            {
+                self.charge(self.stick_direction(gamepad), gesture)
+            }
+            Controller::Mouse(_) => {
+                let cursor = self
+                    .windows
+                    .get_primary()
+                    .and_then(|window| window.cursor_position());
+                if self.mouse_buttons.pressed(MouseButton::Left) {
+                    if let Some(cursor) = cursor {
+                        // a drag of the stick is replaced by the mouse
+                        if !gesture.is_dragging
+                            || self.mouse_buttons.just_pressed(MouseButton::Left)
+                        {
+                            gesture.start_pos = cursor;
+                            gesture.is_dragging = true;
+                        }
+                        gesture.vector = (cursor - gesture.start_pos) * scale;
+                    }
+                    None
+                } else if gesture.is_dragging {
+                    gesture.is_dragging = false;
+                    // the push needs the cursor inside of the window
+                    cursor.map(|_| gesture.vector)
+                } else {
+                    None
+                }
+            }
+            Controller::Keys(gamepad) => {
+                let direction = self.keys_direction() + self.stick_direction(gamepad);
+                self.charge(direction.normalize_or_zero(), gesture)
+            }
+            // the autopilot drags instead, see Autopilot::update
+            Controller::Bot(_) => None,
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{
+    game_assets::GameAssets,
+    lanes::{Lane, LaneView},
+    Crashed, Player, Velocity, PLAY_DIAGONAL_WEIGHTED,
+};
+use bevy::{prelude::*, sprite::collide_aabb::collide};
+
+// relative to the weighted play diagonal
+const MARKER_SIZE: f32 = 0.05;
+// a marker is placed at least this far above the bottom of the view, so it can still be reached
+const MARKER_MARGIN: f32 = 0.08;
+// the space between a marker and the wall above it, more than a player, so the teammate can touch the
+// marker without hitting the wall
+const MARKER_WALL_CLEARANCE: f32 = 0.04;
+
+// component: a downed player can be revived by a teammate touching this, the value is the downed player
+pub struct ReviveMarker(Entity);
+
+// the position of the marker, the downed player is moved there too. after a wall crash the marker
+// is moved below the wall, given by its center and size
+pub fn marker_position(position: Vec2, view: &LaneView, wall: Option<(Vec2, Vec2)>) -> Vec2 {
+    let mut y = position.y;
+    if let Some((wall_center, wall_size)) = wall {
+        let below_wall = wall_center.y
+            - wall_size.y / 2.0
+            - (MARKER_WALL_CLEARANCE + MARKER_SIZE / 2.0) * PLAY_DIAGONAL_WEIGHTED;
+        y = y.min(below_wall);
+    }
+    let min_y = view.camera_y - view.half_height + MARKER_MARGIN * PLAY_DIAGONAL_WEIGHTED;
+    Vec2::new(position.x, y.max(min_y))
+}
+
+// the marker scrolls out of the view with everything else, then the player stays down
+pub fn spawn_revive_marker(
+    commands: &mut Commands,
+    game_assets: &GameAssets,
+    player: Entity,
+    lane: Lane,
+    position: Vec2,
+) {
+    let size = MARKER_SIZE * PLAY_DIAGONAL_WEIGHTED;
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: game_assets.gesture_line_material.clone(),
+            sprite: Sprite::new(Vec2::new(size, size)),
+            // below the downed player
+            transform: Transform::from_translation(position.extend(0.9)),
+            ..Default::default()
+        })
+        .insert(ReviveMarker(player))
+        .insert(lane);
+}
+
+// a live player touching the marker of a downed teammate brings it back at its own position
+pub fn revive_players(
+    mut commands: Commands,
+    mut query_set: QuerySet<(
+        Query<(&Transform, &Sprite, &Lane), (With<Player>, Without<Crashed>)>,
+        Query<(Entity, &Transform, &Sprite, &Lane, &ReviveMarker)>,
+        Query<(&mut Transform, &mut Velocity), (With<Player>, With<Crashed>)>,
+    )>,
+) {
+    let live_players: Vec<(Vec3, Vec2, Lane)> = query_set
+        .q0()
+        .iter()
+        .map(|(tf, sprite, lane)| (tf.translation, sprite.size, *lane))
+        .collect();
+    // the downed player and where it comes back
+    let mut revived = Vec::new();
+    for (entity, tf, sprite, lane, marker) in query_set.q1().iter() {
+        let reviver = live_players.iter().find(|(position, size, player_lane)| {
+            player_lane == lane && collide(*position, *size, tf.translation, sprite.size).is_some()
+        });
+        if let Some((position, _, _)) = reviver {
+            commands.entity(entity).despawn();
+            revived.push((marker.0, *position));
+        }
+    }
+    for (player, position) in revived {
+        if let Ok((mut tf, mut velocity)) = query_set.q2_mut().get_mut(player) {
+            tf.translation.x = position.x;
+            tf.translation.y = position.y;
+            velocity.0 = Vec3::ZERO;
+            commands.entity(player).remove::<Crashed>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marker_is_placed_below_the_wall() {
+        let view = LaneView {
+            camera_y: 0.0,
+            half_height: 380.0,
+        };
+        let wall = (Vec2::new(-200.0, 100.0), Vec2::new(300.0, 18.0));
+        let position = marker_position(Vec2::new(-150.0, 85.0), &view, Some(wall));
+        let marker_top = position.y + MARKER_SIZE * PLAY_DIAGONAL_WEIGHTED / 2.0;
+        let wall_bottom = 100.0 - 9.0;
+        assert!(wall_bottom - marker_top >= MARKER_WALL_CLEARANCE * PLAY_DIAGONAL_WEIGHTED - 1e-3);
+        assert_eq!(position.x, -150.0);
+        // falling behind keeps the marker in the view
+        let position = marker_position(Vec2::new(0.0, -400.0), &view, None);
+        assert!(position.y > -380.0);
+    }
+}
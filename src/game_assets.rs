@@ -0,0 +1,292 @@
+use crate::{art::Art, background::STAR_LAYERS, colorblind::ShapeTextures, theme::Theme, AppState};
+use bevy::{
+    asset::{FileAssetIo, LoadState},
+    prelude::*,
+};
+use bevy_kira_audio::AudioSource;
+use std::path::PathBuf;
+
+// opacity of the ghost relative to the player
+const GHOST_ALPHA: f32 = 0.35;
+
+// where the AssetServer finds a file of the assets folder: next to the executable, or in the
+// crate folder when started by cargo
+pub fn asset_path(path: &str) -> PathBuf {
+    FileAssetIo::get_root_path().join("assets").join(path)
+}
+
+// Resource: every asset of the game, loaded once in AppState::Loading
+pub struct GameAssets {
+    pub font_medium: Handle<Font>,
+    pub font_bold: Handle<Font>,
+    pub food_sound: Handle<AudioSource>,
+    pub gate_sound: Handle<AudioSource>,
+    pub push_sound: Handle<AudioSource>,
+    pub near_miss_sound: Handle<AudioSource>,
+    pub click_sound: Handle<AudioSource>,
+    pub death_sound: Handle<AudioSource>,
+    // music layers with the same length, so they stay in sync
+    pub music_base: Handle<AudioSource>,
+    pub music_intensity: Handle<AudioSource>,
+    pub music_calm: Handle<AudioSource>,
+    pub player_material: Handle<ColorMaterial>,
+    pub player_two_material: Handle<ColorMaterial>,
+    // the translucent player of a ghost run
+    pub ghost_material: Handle<ColorMaterial>,
+    pub food_material: Handle<ColorMaterial>,
+    pub wall_material: Handle<ColorMaterial>,
+    pub gate_material: Handle<ColorMaterial>,
+    pub gesture_line_material: Handle<ColorMaterial>,
+    pub letterbox_material: Handle<ColorMaterial>,
+    pub stamina_bar_material: Handle<ColorMaterial>,
+    pub stamina_bar_background_material: Handle<ColorMaterial>,
+    pub deathscreen_material: Handle<ColorMaterial>,
+    pub button_normal_material: Handle<ColorMaterial>,
+    pub button_hovered_material: Handle<ColorMaterial>,
+    pub button_pressed_material: Handle<ColorMaterial>,
+    // for ui nodes that only lay out their children
+    pub ui_transparent_material: Handle<ColorMaterial>,
+    // one per star layer
+    pub star_materials: Vec<Handle<ColorMaterial>>,
+    pub shape_textures: ShapeTextures,
+    // optional sprite sheets, see art.rs
+    pub art: Art,
+}
+
+impl GameAssets {
+    // every material that is colored by the theme, with its color
+    fn themed_materials(&self, theme: &Theme) -> Vec<(&Handle<ColorMaterial>, Color)> {
+        let star_colors =
+            self.star_materials
+                .iter()
+                .zip(STAR_LAYERS.iter())
+                .map(|(material, layer)| {
+                    let mut color = theme.stars;
+                    color.set_a(theme.stars.a() * layer.alpha);
+                    (material, color)
+                });
+        let mut ghost_color = theme.player;
+        ghost_color.set_a(theme.player.a() * GHOST_ALPHA);
+        let mut materials = vec![
+            (&self.player_material, theme.player),
+            (&self.player_two_material, theme.player_two),
+            (&self.ghost_material, ghost_color),
+            (&self.food_material, theme.food),
+            (&self.wall_material, theme.wall),
+            (&self.gate_material, theme.gate),
+            (&self.gesture_line_material, theme.gesture_line),
+            (&self.letterbox_material, theme.letterbox),
+            (&self.stamina_bar_material, theme.stamina_bar),
+            (
+                &self.stamina_bar_background_material,
+                theme.stamina_bar_background,
+            ),
+            (&self.deathscreen_material, theme.ui_background),
+            (&self.button_normal_material, theme.button_normal),
+            (&self.button_hovered_material, theme.button_hovered),
+            (&self.button_pressed_material, theme.button_pressed),
+        ];
+        materials.extend(star_colors);
+        materials
+    }
+
+    pub fn color_materials(&self, theme: &Theme, materials: &mut Assets<ColorMaterial>) {
+        for (handle, color) in self.themed_materials(theme) {
+            if let Some(material) = materials.get_mut(handle) {
+                material.color = color;
+            }
+        }
+        // food, gates and the player always have a texture, switching between textured and untextured
+        // materials at runtime doesn't update the shaders
+        let textures = &self.shape_textures;
+        let (food_texture, gate_texture) = if theme.shapes {
+            (&textures.food, &textures.gate)
+        } else {
+            (&textures.plain, &textures.plain)
+        };
+        if let Some(material) = materials.get_mut(&self.food_material) {
+            material.texture = Some(food_texture.clone());
+        }
+        if let Some(material) = materials.get_mut(&self.gate_material) {
+            material.texture = Some(gate_texture.clone());
+        }
+        for handle in [
+            &self.player_material,
+            &self.player_two_material,
+            &self.ghost_material,
+        ]
+        .iter()
+        {
+            if let Some(material) = materials.get_mut(*handle) {
+                material.texture = Some(textures.player(theme.player_shape).clone());
+            }
+        }
+    }
+}
+
+// Resource: the files that are still loading, with their paths for error messages
+pub struct LoadingFiles(Vec<(&'static str, HandleUntyped)>);
+
+// components
+pub struct LoadingBar;
+
+pub fn start_loading(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut atlases: ResMut<Assets<TextureAtlas>>,
+    theme: Res<Theme>,
+) {
+    let mut files = Vec::new();
+    let mut load = |path: &'static str| {
+        let handle = asset_server.load_untyped(path);
+        files.push((path, handle.clone()));
+        handle
+    };
+    let font_medium = load("fonts/BaiJamjuree-Medium.ttf").typed();
+    let font_bold = load("fonts/BaiJamjuree-Bold.ttf").typed();
+    let food_sound = load("sounds/pickupCoin.wav").typed();
+    let gate_sound = load("sounds/gate.wav").typed();
+    let push_sound = load("sounds/push.wav").typed();
+    let near_miss_sound = load("sounds/near_miss.wav").typed();
+    let click_sound = load("sounds/click.wav").typed();
+    let death_sound = load("sounds/death.wav").typed();
+    let music_base = load("music/base.wav").typed();
+    let music_intensity = load("music/intensity.wav").typed();
+    let music_calm = load("music/calm.wav").typed();
+    let (art, art_files) = Art::load(&asset_server, &mut atlases);
+    files.extend(art_files);
+    commands.insert_resource(LoadingFiles(files));
+
+    // one material per role, the colors come from the theme
+    let mut material = || materials.add(ColorMaterial::default());
+    let game_assets = GameAssets {
+        font_medium,
+        font_bold,
+        food_sound,
+        gate_sound,
+        push_sound,
+        near_miss_sound,
+        click_sound,
+        death_sound,
+        music_base,
+        music_intensity,
+        music_calm,
+        player_material: material(),
+        player_two_material: material(),
+        ghost_material: material(),
+        food_material: material(),
+        wall_material: material(),
+        gate_material: material(),
+        gesture_line_material: material(),
+        letterbox_material: material(),
+        stamina_bar_material: material(),
+        stamina_bar_background_material: material(),
+        deathscreen_material: material(),
+        button_normal_material: material(),
+        button_hovered_material: material(),
+        button_pressed_material: material(),
+        ui_transparent_material: materials.add(Color::NONE.into()),
+        star_materials: STAR_LAYERS.iter().map(|_| material()).collect(),
+        shape_textures: ShapeTextures::new(&mut textures),
+        art,
+    };
+    game_assets.color_materials(&theme, &mut materials);
+    commands.insert_resource(game_assets);
+
+    // progress bar, fonts may not be there yet so no text
+    commands.spawn_bundle(UiCameraBundle::default());
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(theme.ui_background.into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(50.0), Val::Percent(3.0)),
+                        ..Default::default()
+                    },
+                    material: materials.add(theme.stamina_bar_background.into()),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                                ..Default::default()
+                            },
+                            material: materials.add(theme.text_secondary.into()),
+                            ..Default::default()
+                        })
+                        .insert(LoadingBar);
+                });
+        });
+}
+
+pub fn check_loading(
+    asset_server: Res<AssetServer>,
+    loading_files: Res<LoadingFiles>,
+    mut app_state: ResMut<State<AppState>>,
+    mut query: Query<(&mut Style, &Handle<ColorMaterial>), With<LoadingBar>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut windows: ResMut<Windows>,
+    mut reported: Local<bool>,
+) {
+    let mut loaded = 0;
+    let mut missing = Vec::new();
+    for (path, handle) in loading_files.0.iter() {
+        match asset_server.get_load_state(handle.id) {
+            LoadState::Loaded => loaded += 1,
+            LoadState::Failed => missing.push(*path),
+            _ => {}
+        }
+    }
+    let progress = loaded as f32 / loading_files.0.len() as f32;
+    if let Ok((mut style, material)) = query.single_mut() {
+        style.size.width = Val::Percent(100.0 * progress);
+        if !missing.is_empty() {
+            // fill the bar in red so it is obvious that the game is stuck
+            style.size.width = Val::Percent(100.0);
+            if let Some(material) = materials.get_mut(material) {
+                material.color = Color::rgb_u8(0xEF, 0x44, 0x44);
+            }
+        }
+    }
+
+    if !missing.is_empty() {
+        if !*reported {
+            *reported = true;
+            let message = format!("Missing assets: {}", missing.join(", "));
+            error!(
+                "{} (the assets folder has to be next to the executable, or in CARGO_MANIFEST_DIR when \
+                 started by cargo, it was looked up in {})",
+                message,
+                FileAssetIo::get_root_path().display()
+            );
+            if let Some(window) = windows.get_primary_mut() {
+                window.set_title(format!("Endless game - {}", message));
+            }
+        }
+        return;
+    }
+    if loaded == loading_files.0.len() {
+        app_state.set(AppState::Menu.into());
+    }
+}
+
+pub fn exit_loading(mut commands: Commands, query: Query<Entity>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<LoadingFiles>();
+}
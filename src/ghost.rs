@@ -0,0 +1,335 @@
+use crate::{
+    bot,
+    game_assets::GameAssets,
+    lanes::{GameMode, Lane, LaneViews},
+    leaderboard_protocol::ReplaySample,
+    settings::Settings,
+    stats::RunStats,
+    storage,
+    theme::{Theme, ThemeText},
+    Crashed, Player, Score, PLAY_DIAGONAL_WEIGHTED, PLAY_HEIGHT,
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+const GHOST_FILE: &str = "ghost.ron";
+// seconds between two recorded positions
+const SAMPLE_INTERVAL: f32 = 0.05;
+
+// a recorded run, the walls come from the seed, so a new run on it meets the same walls.
+// the samples are the replay that is submitted to the leaderboard
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GhostRun {
+    pub seed: u64,
+    pub score: i32,
+    pub samples: Vec<ReplaySample>,
+}
+
+impl GhostRun {
+    // between the two samples around the time, None once the ghost crashed
+    fn position_at(&self, time: f32) -> Option<Vec2> {
+        let next = self.samples.iter().position(|sample| sample.time > time)?;
+        let b = self.samples[next];
+        let b_position = Vec2::new(b.x, b.y);
+        if next == 0 {
+            return Some(b_position);
+        }
+        let a = self.samples[next - 1];
+        let t = ((time - a.time) / (b.time - a.time)).clamp(0.0, 1.0);
+        Some(Vec2::new(a.x, a.y).lerp(b_position, t))
+    }
+
+    fn score_at(&self, time: f32) -> i32 {
+        self.samples
+            .iter()
+            .take_while(|sample| sample.time <= time)
+            .last()
+            .map_or(0, |sample| sample.score)
+    }
+}
+
+// Resource: the best run, stored in save/ghost.ron, and the ghost the next single runs race against
+pub struct Ghosts {
+    best: GhostRun,
+    race: Option<GhostRun>,
+}
+
+impl Ghosts {
+    pub fn load() -> Self {
+        let best: GhostRun = storage::load_or_default(GHOST_FILE);
+        let race = Some(best.clone()).filter(|run| !run.samples.is_empty());
+        Ghosts { best, race }
+    }
+
+    // single runs with a ghost use its seed
+    pub fn seed(&self, mode: GameMode) -> Option<u64> {
+        match mode {
+            GameMode::Single => self.race.as_ref().map(|run| run.seed),
+            _ => None,
+        }
+    }
+}
+
+// Resource: the run that is being recorded
+pub struct GhostRecorder {
+    time: f32,
+    next_sample: f32,
+    run: GhostRun,
+}
+
+impl GhostRecorder {
+    pub fn run(&self) -> &GhostRun {
+        &self.run
+    }
+}
+
+// components
+pub struct GhostPlayer;
+// how far the player is ahead of the ghost
+pub struct GhostDelta;
+
+// starts the recording and spawns the ghost, only single runs have one
+pub fn spawn_ghost(
+    commands: &mut Commands,
+    game_assets: &GameAssets,
+    theme: &Theme,
+    ghosts: &Ghosts,
+    mode: GameMode,
+    seed: u64,
+) {
+    commands.insert_resource(GhostRecorder {
+        time: 0.0,
+        next_sample: 0.0,
+        run: GhostRun {
+            seed,
+            ..Default::default()
+        },
+    });
+    if mode != GameMode::Single || ghosts.race.is_none() {
+        return;
+    }
+    let ghost = spawn_ghost_sprite(commands, game_assets);
+    commands.entity(ghost).insert(GhostPlayer);
+    let delta = spawn_hud_line(commands, game_assets, theme);
+    commands.entity(delta).insert(GhostDelta);
+}
+
+// a ghost player, also used for the opponent in versus
+pub fn spawn_ghost_sprite(commands: &mut Commands, game_assets: &GameAssets) -> Entity {
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: game_assets.ghost_material.clone(),
+            sprite: Sprite::new(Vec2::new(
+                0.03 * PLAY_DIAGONAL_WEIGHTED,
+                0.03 * PLAY_DIAGONAL_WEIGHTED,
+            )),
+            // below the player and above its trail
+            transform: Transform::from_xyz(0.0, 0.0, 0.95),
+            ..Default::default()
+        })
+        .id()
+}
+
+// a text line below the scoreboard that follows the view, see hud_line_y
+pub fn spawn_hud_line(commands: &mut Commands, game_assets: &GameAssets, theme: &Theme) -> Entity {
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text {
+                alignment: TextAlignment {
+                    horizontal: HorizontalAlign::Center,
+                    vertical: VerticalAlign::Top,
+                },
+                sections: vec![TextSection {
+                    value: String::new(),
+                    style: TextStyle {
+                        font_size: PLAY_DIAGONAL_WEIGHTED * 0.05,
+                        font: game_assets.font_medium.clone(),
+                        color: theme.text_secondary,
+                    },
+                }],
+            },
+            transform: Transform::from_xyz(0.0, 0.0, 2.0),
+            ..Default::default()
+        })
+        .insert(ThemeText::Secondary)
+        .id()
+}
+
+// below the scoreboard of the first lane
+pub fn hud_line_y(lane_views: &LaneViews) -> f32 {
+    lane_views.get(&Lane(0)).camera_y - PLAY_HEIGHT / 2.5 - 0.09 * PLAY_DIAGONAL_WEIGHTED
+}
+
+pub fn record_ghost(
+    time: Res<Time>,
+    mode: Res<GameMode>,
+    run_stats: Res<RunStats>,
+    mut recorder: ResMut<GhostRecorder>,
+    player_query: Query<(&Transform, &Score), (With<Player>, Without<Crashed>)>,
+) {
+    if *mode != GameMode::Single {
+        return;
+    }
+    recorder.time += time.delta_seconds();
+    if recorder.time < recorder.next_sample {
+        return;
+    }
+    recorder.next_sample += SAMPLE_INTERVAL;
+    if let Ok((tf, score)) = player_query.single() {
+        let sample = ReplaySample {
+            time: recorder.time,
+            x: tf.translation.x,
+            y: tf.translation.y,
+            score: score.0,
+            gates: run_stats.gates_passed,
+        };
+        recorder.run.score = score.0;
+        recorder.run.samples.push(sample);
+    }
+}
+
+// the ghost follows its recorded path and disappears where it crashed
+pub fn replay_ghost(
+    recorder: Res<GhostRecorder>,
+    ghosts: Res<Ghosts>,
+    mut ghost_query: Query<(&mut Transform, &mut Visible), With<GhostPlayer>>,
+) {
+    let position = ghosts
+        .race
+        .as_ref()
+        .and_then(|run| run.position_at(recorder.time));
+    for (mut tf, mut visible) in ghost_query.iter_mut() {
+        visible.is_visible = position.is_some();
+        if let Some(position) = position {
+            tf.translation.x = position.x;
+            tf.translation.y = position.y;
+        }
+    }
+}
+
+// below the scoreboard
+pub fn update_ghost_delta(
+    recorder: Res<GhostRecorder>,
+    ghosts: Res<Ghosts>,
+    lane_views: Res<LaneViews>,
+    player_query: Query<&Score, With<Player>>,
+    mut delta_query: Query<(&mut Transform, &mut Text), With<GhostDelta>>,
+) {
+    let ghost_score = match ghosts.race.as_ref() {
+        Some(run) => run.score_at(recorder.time),
+        None => return,
+    };
+    let score = player_query.single().map_or(0, |score| score.0);
+    for (mut tf, mut text) in delta_query.iter_mut() {
+        if let Some(section) = text.sections.get_mut(0) {
+            section.value = match score - ghost_score {
+                0 => "Even with ghost".to_string(),
+                delta if delta > 0 => format!("{} ahead of ghost", delta),
+                delta => format!("{} behind ghost", -delta),
+            };
+        }
+        tf.translation.y = hud_line_y(&lane_views);
+    }
+}
+
+// a new best run is stored and raced from now on, unless a loaded ghost is still better
+pub fn save_ghost(
+    mode: Res<GameMode>,
+    settings: Res<Settings>,
+    recorder: Res<GhostRecorder>,
+    mut ghosts: ResMut<Ghosts>,
+) {
+    let run = &recorder.run;
+    if *mode != GameMode::Single || run.samples.is_empty() || bot::skill_of(&settings, 0).is_some()
+    {
+        return;
+    }
+    if ghosts.best.samples.is_empty() || run.score > ghosts.best.score {
+        ghosts.best = run.clone();
+        storage::save(GHOST_FILE, &ghosts.best);
+    }
+    let beaten = match ghosts.race.as_ref() {
+        Some(race) => run.score > race.score,
+        None => true,
+    };
+    if beaten {
+        ghosts.race = Some(run.clone());
+    }
+}
+
+// dropping a ghost file onto the window races it in the next single runs
+pub fn load_dropped_ghost(
+    mut ev_file_drop: EventReader<FileDragAndDrop>,
+    mut ghosts: ResMut<Ghosts>,
+) {
+    for ev in ev_file_drop.iter() {
+        let path = match ev {
+            FileDragAndDrop::DroppedFile { path_buf, .. } => path_buf,
+            _ => continue,
+        };
+        let run = fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|content| {
+                ron::de::from_str::<GhostRun>(&content).map_err(|err| err.to_string())
+            });
+        match run {
+            Ok(run) if !run.samples.is_empty() => {
+                eprintln!(
+                    "Racing the ghost from {} with score {}",
+                    path.display(),
+                    run.score
+                );
+                ghosts.race = Some(run);
+            }
+            Ok(_) => eprintln!("{} has no recorded run", path.display()),
+            Err(err) => eprintln!("Could not load ghost {}: {}", path.display(), err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run() -> GhostRun {
+        let sample = |time, x, y, score| ReplaySample {
+            time,
+            x,
+            y,
+            score,
+            gates: 0,
+        };
+        GhostRun {
+            seed: 7,
+            score: 3,
+            samples: vec![
+                sample(0.0, 0.0, 0.0, 0),
+                sample(1.0, 10.0, 20.0, 1),
+                sample(2.0, 10.0, 40.0, 3),
+            ],
+        }
+    }
+
+    #[test]
+    fn position_is_interpolated_between_samples() {
+        let run = run();
+        assert_eq!(run.position_at(-1.0), Some(Vec2::ZERO));
+        assert_eq!(run.position_at(0.5), Some(Vec2::new(5.0, 10.0)));
+        assert_eq!(run.position_at(1.0), Some(Vec2::new(10.0, 20.0)));
+        assert_eq!(run.position_at(1.75), Some(Vec2::new(10.0, 35.0)));
+        // the ghost crashed after its last sample
+        assert_eq!(run.position_at(2.0), None);
+        assert_eq!(GhostRun::default().position_at(0.0), None);
+    }
+
+    #[test]
+    fn score_is_the_one_of_the_last_sample() {
+        let run = run();
+        assert_eq!(run.score_at(-1.0), 0);
+        assert_eq!(run.score_at(0.99), 0);
+        assert_eq!(run.score_at(1.0), 1);
+        assert_eq!(run.score_at(10.0), 3);
+    }
+}
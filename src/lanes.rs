@@ -0,0 +1,198 @@
+use crate::{camera, WindowSize, PLAY_DIAGONAL_WEIGHTED, PLAY_HEIGHT};
+use bevy::{
+    prelude::*,
+    render::{
+        camera::ActiveCameras,
+        pass::{
+            LoadOp, Operations, PassDescriptor, RenderPassDepthStencilAttachmentDescriptor,
+            TextureAttachment,
+        },
+        render_graph::{
+            base::{self, MainPass},
+            CameraNode, PassNode, RenderGraph, WindowSwapChainNode, WindowTextureNode,
+        },
+    },
+};
+
+// distance between the lanes in world units, far enough that they never see each other
+const LANE_SPACING: f32 = 40.0 * PLAY_DIAGONAL_WEIGHTED;
+// the camera of the right half of the window in split screen
+pub const RIGHT_CAMERA: &str = "Camera2dRight";
+const RIGHT_CAMERA_NODE: &str = "camera_2d_right";
+const RIGHT_PASS: &str = "right_pass";
+
+// component: every player has its own lane with its own camera, walls, gates and food.
+// all lanes are in the same world, side by side
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Lane(pub usize);
+
+impl Lane {
+    // the center of the lane
+    pub fn x(&self) -> f32 {
+        self.0 as f32 * LANE_SPACING
+    }
+}
+
+// Resource: chosen in the menu, kept for the respawn button
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameMode {
+    Single,
+    // two players race side by side, the left one with the mouse, the right one with the keyboard or a gamepad
+    SplitScreen,
+    // two players share one lane, controlled like in split screen
+    Coop,
+    // against another instance of the game over the network, see versus.rs
+    Versus,
+}
+
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::Single
+    }
+}
+
+impl GameMode {
+    pub fn lanes(&self) -> usize {
+        match self {
+            GameMode::Single | GameMode::Coop | GameMode::Versus => 1,
+            GameMode::SplitScreen => 2,
+        }
+    }
+
+    pub fn players_per_lane(&self) -> usize {
+        match self {
+            GameMode::Single | GameMode::SplitScreen | GameMode::Versus => 1,
+            GameMode::Coop => 2,
+        }
+    }
+}
+
+// where the camera of a lane looks, updated by camera::camera_movement
+#[derive(Clone, Copy)]
+pub struct LaneView {
+    pub camera_y: f32,
+    pub half_height: f32,
+}
+
+impl Default for LaneView {
+    fn default() -> Self {
+        LaneView {
+            camera_y: 0.0,
+            half_height: PLAY_HEIGHT / 2.0,
+        }
+    }
+}
+
+// Resource: one view per lane
+pub struct LaneViews(pub Vec<LaneView>);
+
+impl LaneViews {
+    pub fn new(mode: GameMode) -> Self {
+        LaneViews(vec![LaneView::default(); mode.lanes()])
+    }
+
+    pub fn get(&self, lane: &Lane) -> LaneView {
+        self.0.get(lane.0).copied().unwrap_or_default()
+    }
+}
+
+// world units per pixel, every lane gets an equal part of the window
+pub fn lane_scale(window_size: &WindowSize, mode: GameMode, zoom: f32) -> f32 {
+    let lane_width = window_size.width / mode.lanes() as f32;
+    camera::view_scale(lane_width, window_size.height) * zoom
+}
+
+// the camera is moved sideways so the lane is centered in its part of the window
+pub fn camera_x(lane: &Lane, mode: GameMode, window_size: &WindowSize, scale: f32) -> f32 {
+    let window_width = window_size.width * scale;
+    let offset = match (mode, lane.0) {
+        (GameMode::Single, _) | (GameMode::Coop, _) | (GameMode::Versus, _) => 0.0,
+        (GameMode::SplitScreen, 0) => window_width / 4.0,
+        (GameMode::SplitScreen, _) => -window_width / 4.0,
+    };
+    lane.x() + offset
+}
+
+// the first lane is drawn by the default 2d camera, the second one by the right camera
+pub fn camera_name(lane: &Lane) -> Option<String> {
+    if lane.0 == 0 {
+        None
+    } else {
+        Some(RIGHT_CAMERA.to_string())
+    }
+}
+
+// the right camera draws after the main pass without clearing it, its letterbox covers only the right half.
+// the pass is added when split screen is played for the first time; the render graph can't remove
+// nodes, so later games in the other modes keep an empty pass without a camera that draws nothing
+pub fn add_right_pass(
+    mode: Res<GameMode>,
+    mut graph: ResMut<RenderGraph>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    msaa: Res<Msaa>,
+) {
+    if *mode != GameMode::SplitScreen || graph.get_node_id(RIGHT_PASS).is_ok() {
+        return;
+    }
+    let mut right_pass = PassNode::<&MainPass>::new(PassDescriptor {
+        color_attachments: vec![msaa.color_attachment_descriptor(
+            TextureAttachment::Input("color_attachment".to_string()),
+            TextureAttachment::Input("color_resolve_target".to_string()),
+            Operations {
+                load: LoadOp::Load,
+                store: true,
+            },
+        )],
+        depth_stencil_attachment: Some(RenderPassDepthStencilAttachmentDescriptor {
+            attachment: TextureAttachment::Input("depth".to_string()),
+            depth_ops: Some(Operations {
+                load: LoadOp::Clear(1.0),
+                store: true,
+            }),
+            stencil_ops: None,
+        }),
+        sample_count: msaa.samples,
+    });
+    right_pass.add_camera(RIGHT_CAMERA);
+    active_cameras.add(RIGHT_CAMERA);
+    graph.add_node(RIGHT_PASS, right_pass);
+    graph.add_system_node(RIGHT_CAMERA_NODE, CameraNode::new(RIGHT_CAMERA));
+    graph.add_node_edge(RIGHT_CAMERA_NODE, RIGHT_PASS).unwrap();
+    graph
+        .add_slot_edge(
+            base::node::PRIMARY_SWAP_CHAIN,
+            WindowSwapChainNode::OUT_TEXTURE,
+            RIGHT_PASS,
+            if msaa.samples > 1 {
+                "color_resolve_target"
+            } else {
+                "color_attachment"
+            },
+        )
+        .unwrap();
+    graph
+        .add_slot_edge(
+            base::node::MAIN_DEPTH_TEXTURE,
+            WindowTextureNode::OUT_TEXTURE,
+            RIGHT_PASS,
+            "depth",
+        )
+        .unwrap();
+    if msaa.samples > 1 {
+        graph
+            .add_slot_edge(
+                base::node::MAIN_SAMPLED_COLOR_ATTACHMENT,
+                WindowSwapChainNode::OUT_TEXTURE,
+                RIGHT_PASS,
+                "color_attachment",
+            )
+            .unwrap();
+    }
+    // after the left half and before the ui
+    graph
+        .add_node_edge(base::node::MAIN_PASS, RIGHT_PASS)
+        .unwrap();
+    graph
+        .add_node_edge(RIGHT_PASS, bevy::ui::node::UI_PASS)
+        .unwrap();
+}
@@ -0,0 +1,200 @@
+use crate::{
+    bot,
+    ghost::GhostRecorder,
+    lanes::GameMode,
+    leaderboard_protocol::{self, Entry, ErrorResponse, Submission, SubmitResult},
+    settings::Settings,
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+// how long a request may take before the leaderboard counts as unavailable
+const TIMEOUT: Duration = Duration::from_secs(5);
+// entries shown on the screens
+const SHOWN_ENTRIES: usize = 5;
+
+// an empty url turns the leaderboard off
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeaderboardSettings {
+    // like http://127.0.0.1:7878, see src/bin/leaderboard_server.rs
+    pub url: String,
+    // the name the scores are submitted with
+    pub name: String,
+}
+
+impl Default for LeaderboardSettings {
+    fn default() -> Self {
+        LeaderboardSettings {
+            url: String::new(),
+            name: "Player".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum LeaderboardStatus {
+    Off,
+    Loading,
+    Failed,
+    // rank of the submitted run, if there was one
+    Loaded {
+        rank: Option<usize>,
+        top: Vec<Entry>,
+    },
+}
+
+// Resource: requests run on their own thread and write their result into the status.
+// every request gets a new status, so an old request can't overwrite a newer one
+pub struct Leaderboard(Arc<Mutex<LeaderboardStatus>>);
+
+impl Default for Leaderboard {
+    fn default() -> Self {
+        Leaderboard(Arc::new(Mutex::new(LeaderboardStatus::Off)))
+    }
+}
+
+impl Leaderboard {
+    fn start<F>(&mut self, request: F)
+    where
+        F: FnOnce() -> Result<LeaderboardStatus, String> + Send + 'static,
+    {
+        let status = Arc::new(Mutex::new(LeaderboardStatus::Loading));
+        self.0 = status.clone();
+        thread::spawn(move || {
+            let result = request().unwrap_or_else(|err| {
+                eprintln!("Leaderboard request failed: {}", err);
+                LeaderboardStatus::Failed
+            });
+            if let Ok(mut status) = status.lock() {
+                *status = result;
+            }
+        });
+    }
+
+    fn status(&self) -> LeaderboardStatus {
+        self.0
+            .lock()
+            .map(|status| status.clone())
+            .unwrap_or(LeaderboardStatus::Failed)
+    }
+}
+
+// component: text that shows the leaderboard
+pub struct LeaderboardText;
+
+// sends a request to the server and returns the body of a successful response
+fn request(url: &str, method: &str, path: &str, body: &str) -> Result<String, String> {
+    let host = url.trim_start_matches("http://").trim_end_matches('/');
+    let address = host
+        .to_socket_addrs()
+        .map_err(|err| err.to_string())?
+        .next()
+        .ok_or_else(|| format!("{} has no address", host))?;
+    let mut stream =
+        TcpStream::connect_timeout(&address, TIMEOUT).map_err(|err| err.to_string())?;
+    stream
+        .set_read_timeout(Some(TIMEOUT))
+        .map_err(|err| err.to_string())?;
+    let head = format!("{} {} HTTP/1.1\r\nHost: {}", method, path, host);
+    leaderboard_protocol::write_message(&mut stream, &head, body).map_err(|err| err.to_string())?;
+    let response =
+        leaderboard_protocol::read_message(&mut stream).map_err(|err| err.to_string())?;
+    if response.target() == "200" {
+        Ok(response.body)
+    } else {
+        Err(serde_json::from_str::<ErrorResponse>(&response.body)
+            .map(|response| response.error)
+            .unwrap_or(response.start_line))
+    }
+}
+
+fn fetch(leaderboard: &mut Leaderboard, url: String) {
+    leaderboard.start(move || {
+        let body = request(&url, "GET", "/scores", "")?;
+        let top: Vec<Entry> = serde_json::from_str(&body).map_err(|err| err.to_string())?;
+        Ok(LeaderboardStatus::Loaded { rank: None, top })
+    });
+}
+
+// the high-score screen shows the best entries
+pub fn fetch_top(settings: Res<Settings>, mut leaderboard: ResMut<Leaderboard>) {
+    let url = settings.leaderboard.url.clone();
+    if url.is_empty() {
+        *leaderboard = Leaderboard::default();
+        return;
+    }
+    fetch(&mut leaderboard, url);
+}
+
+// single runs are submitted with their seed and replay, other modes and bot runs only show the best entries
+pub fn submit_run(
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    recorder: Res<GhostRecorder>,
+    mut leaderboard: ResMut<Leaderboard>,
+) {
+    let url = settings.leaderboard.url.clone();
+    if url.is_empty() {
+        *leaderboard = Leaderboard::default();
+        return;
+    }
+    let run = recorder.run();
+    if *mode != GameMode::Single || run.samples.is_empty() || bot::skill_of(&settings, 0).is_some()
+    {
+        fetch(&mut leaderboard, url);
+        return;
+    }
+    let submission = Submission {
+        name: settings.leaderboard.name.clone(),
+        score: run.score,
+        seed: run.seed,
+        replay: run.samples.clone(),
+    };
+    leaderboard.start(move || {
+        let body = serde_json::to_string(&submission).map_err(|err| err.to_string())?;
+        let body = request(&url, "POST", "/scores", &body)?;
+        let result: SubmitResult = serde_json::from_str(&body).map_err(|err| err.to_string())?;
+        Ok(LeaderboardStatus::Loaded {
+            rank: Some(result.rank),
+            top: result.top,
+        })
+    });
+}
+
+pub fn update_leaderboard_text(
+    leaderboard: Res<Leaderboard>,
+    mut query: Query<&mut Text, With<LeaderboardText>>,
+) {
+    let value = match leaderboard.status() {
+        LeaderboardStatus::Off => String::new(),
+        LeaderboardStatus::Loading => "Loading leaderboard...".to_string(),
+        LeaderboardStatus::Failed => "Leaderboard unavailable".to_string(),
+        LeaderboardStatus::Loaded { rank, top } => {
+            let mut lines = Vec::new();
+            if let Some(rank) = rank {
+                lines.push(format!("Leaderboard rank {}", rank));
+            }
+            for (index, entry) in top.iter().take(SHOWN_ENTRIES).enumerate() {
+                lines.push(format!("{}. {} {}", index + 1, entry.name, entry.score));
+            }
+            if top.is_empty() {
+                lines.push("No scores yet".to_string());
+            }
+            lines.join("\n")
+        }
+    };
+    for mut text in query.iter_mut() {
+        if let Some(section) = text.sections.get_mut(0) {
+            if section.value != value {
+                section.value = value.clone();
+            }
+        }
+    }
+}
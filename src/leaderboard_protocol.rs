@@ -0,0 +1,438 @@
+// the leaderboard protocol, shared by the game and the reference server in src/bin.
+// GET /scores returns the best entries, POST /scores takes a Submission and returns a SubmitResult,
+// failed requests return an ErrorResponse. everything is JSON over HTTP/1.1
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+// the level, must match the walls in main.rs
+pub const LEVEL_WIDTH: f32 = 720.0;
+// the height of the view at the normal zoom, must match PLAY_HEIGHT in main.rs
+const VIEW_HEIGHT: f32 = 760.0;
+// the view scrolls at most this fast at the start of a run, in world units per second, and gets
+// faster by CAMERA_SPEEDUP_PER_POINT for every point, must match camera_movement in camera.rs
+const CAMERA_BASE_SPEED: f32 = 128.0 * 740.27 / 720.0;
+const CAMERA_SPEEDUP_PER_POINT: f32 = 0.025;
+// the largest look_ahead and zoom_out camera settings a run is verified for
+const MAX_LOOK_AHEAD: f32 = 3.0;
+const MAX_ZOOM_OUT: f32 = 0.5;
+// the ghost recorder samples every 0.05 seconds, longer gaps only come from very slow frames
+const MAX_SAMPLE_GAP: f32 = 0.5;
+// the middle of a gap is this far from the center at most, relative to the level width
+const GAP_RANGE: f32 = 0.3;
+pub const GAP_WIDTH: f32 = 0.2;
+// the gates are fixed in the world, so every run on a seed meets them at the same heights.
+// the first one is this far above the start, in world units
+const FIRST_GATE_Y: f32 = 640.0;
+// the distance to the next gate shrinks with every gate until it reaches the minimum
+const GATE_SPACING: f32 = 256.0;
+const GATE_SPACING_STEP: f32 = 8.0;
+const MIN_GATE_SPACING: f32 = 160.0;
+// every gate is moved up by a random offset up to this
+const GATE_Y_RANGE: f32 = 32.0;
+// a gate counts as passed if the player was this close to it, in world units
+const GATE_TOLERANCE: f32 = 36.0;
+// every point that doesn't come from a gate comes from food, a player can't eat faster than this
+const MAX_FOOD_PER_SECOND: f32 = 3.0;
+// food eaten right at the start
+const FOOD_ALLOWANCE: f32 = 2.0;
+// longer messages are refused, in bytes
+const MAX_HEAD_SIZE: u64 = 8 * 1024;
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+// the number of entries the server returns
+pub const TOP_ENTRIES: usize = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub score: i32,
+    pub seed: u64,
+}
+
+// where the player was at a moment of the run, time in seconds since the start, and how many gates
+// it passed until then. ghost runs store the same samples
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ReplaySample {
+    pub time: f32,
+    pub x: f32,
+    pub y: f32,
+    pub score: i32,
+    pub gates: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Submission {
+    pub name: String,
+    pub score: i32,
+    pub seed: u64,
+    pub replay: Vec<ReplaySample>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitResult {
+    // starting at 1
+    pub rank: usize,
+    pub top: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+// a gate and the walls next to it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GateLayout {
+    // the middle of the gap relative to the level width
+    pub gap_middle: f32,
+    // in world units
+    pub y: f32,
+}
+
+impl GateLayout {
+    // the left and right end of the gap in world units
+    pub fn gap(&self) -> (f32, f32) {
+        (
+            (self.gap_middle - GAP_WIDTH / 2.0) * LEVEL_WIDTH,
+            (self.gap_middle + GAP_WIDTH / 2.0) * LEVEL_WIDTH,
+        )
+    }
+}
+
+// the gates of a run, the same for every run on the seed no matter how it is played
+pub struct GateSequence {
+    rng: StdRng,
+    index: u32,
+    base_y: f32,
+}
+
+impl GateSequence {
+    pub fn new(seed: u64) -> Self {
+        GateSequence {
+            rng: StdRng::seed_from_u64(seed),
+            index: 0,
+            base_y: FIRST_GATE_Y,
+        }
+    }
+}
+
+impl Iterator for GateSequence {
+    type Item = GateLayout;
+
+    fn next(&mut self) -> Option<GateLayout> {
+        let gap_middle = self.rng.gen_range(-GAP_RANGE..GAP_RANGE);
+        let y = self.base_y + self.rng.gen_range(0.0..GATE_Y_RANGE);
+        self.base_y += (GATE_SPACING - GATE_SPACING_STEP * self.index as f32).max(MIN_GATE_SPACING);
+        self.index += 1;
+        Some(GateLayout { gap_middle, y })
+    }
+}
+
+// the fastest the view can scroll with the score
+fn max_view_speed(score: i32) -> f32 {
+    CAMERA_BASE_SPEED
+        * (1.0 + CAMERA_SPEEDUP_PER_POINT * score.max(0) as f32)
+        * (1.0 + MAX_LOOK_AHEAD)
+}
+
+// re-creates the walls of the seed and checks the replay against them: the player can't get above
+// the view, which scrolls at a limited speed, the straight path between two samples has to go
+// through the gap of every wall it crosses and every counted gate has to be reached
+pub fn verify(submission: &Submission) -> Result<(), String> {
+    let samples = &submission.replay;
+    let last = samples.last().ok_or("the replay is empty")?;
+    if last.score != submission.score {
+        return Err("the score does not match the replay".to_string());
+    }
+    if samples[0].gates != 0 || samples[0].score != 0 {
+        return Err("the replay does not start at the beginning".to_string());
+    }
+    let mut sequence = GateSequence::new(submission.seed);
+    let mut gates: Vec<GateLayout> = Vec::new();
+    // the player starts in the middle of the bottom of the level
+    let start = ReplaySample {
+        time: 0.0,
+        x: 0.0,
+        y: 0.0,
+        score: 0,
+        gates: 0,
+    };
+    let mut view_top = VIEW_HEIGHT / 2.0 * (1.0 + MAX_ZOOM_OUT);
+    let mut highest = start.y;
+    for (previous, sample) in std::iter::once(&start).chain(samples).zip(samples) {
+        if sample.time < previous.time
+            || sample.score < previous.score
+            || sample.gates < previous.gates
+        {
+            return Err("the replay goes backwards".to_string());
+        }
+        if sample.time - previous.time > MAX_SAMPLE_GAP {
+            return Err("the replay has a gap".to_string());
+        }
+        if sample.x.abs() > LEVEL_WIDTH / 2.0 {
+            return Err("the replay leaves the level".to_string());
+        }
+        if sample.score - previous.score < (sample.gates - previous.gates) as i32 {
+            return Err("the replay passes gates without scoring".to_string());
+        }
+        let food_score = (sample.score - sample.gates as i32) as f32;
+        if food_score > MAX_FOOD_PER_SECOND * sample.time + FOOD_ALLOWANCE {
+            return Err("the replay eats food faster than possible".to_string());
+        }
+        view_top += max_view_speed(sample.score) * (sample.time - previous.time);
+        if sample.y > view_top {
+            return Err("the replay moves faster than the view scrolls".to_string());
+        }
+        highest = highest.max(sample.y);
+        while gates.len() < sample.gates as usize
+            || !matches!(gates.last(), Some(gate) if gate.y > highest)
+        {
+            gates.push(sequence.next().unwrap());
+        }
+        // where the path crosses the height of a wall, it has to be in the gap
+        let (low, high) = (previous.y.min(sample.y), previous.y.max(sample.y));
+        for (index, gate) in gates.iter().enumerate() {
+            if gate.y <= low || gate.y > high {
+                continue;
+            }
+            let t = (gate.y - previous.y) / (sample.y - previous.y);
+            let x = previous.x + (sample.x - previous.x) * t;
+            let (left, right) = gate.gap();
+            if x < left - GATE_TOLERANCE || x > right + GATE_TOLERANCE {
+                return Err(format!(
+                    "the replay goes through the wall of gate {}",
+                    index + 1
+                ));
+            }
+        }
+        for index in previous.gates..sample.gates {
+            if highest + GATE_TOLERANCE < gates[index as usize].y {
+                return Err(format!("gate {} was not passed", index + 1));
+            }
+        }
+    }
+    Ok(())
+}
+
+// a request or a response
+pub struct HttpMessage {
+    // the request line or the status line
+    pub start_line: String,
+    pub body: String,
+}
+
+impl HttpMessage {
+    // the path of a request or the status code of a response
+    pub fn target(&self) -> &str {
+        self.start_line.split_whitespace().nth(1).unwrap_or("")
+    }
+}
+
+// reads the head and as much of the body as the content length says
+pub fn read_message(stream: &mut impl Read) -> io::Result<HttpMessage> {
+    let mut reader = BufReader::new(stream.take(MAX_HEAD_SIZE + MAX_BODY_SIZE as u64));
+    let mut start_line = String::new();
+    reader.read_line(&mut start_line)?;
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the body is larger than {} bytes", MAX_BODY_SIZE),
+        ));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(HttpMessage {
+        start_line: start_line.trim_end().to_string(),
+        body: String::from_utf8_lossy(&body).to_string(),
+    })
+}
+
+// head is the start line with any extra header lines, the connection is closed afterwards
+pub fn write_message(stream: &mut impl Write, head: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        head,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // samples like the ghost recorder takes them
+    const STEPS_PER_SECOND: u32 = 20;
+
+    // goes straight through the middle of every gate of the seed, one gate per second
+    fn replay(seed: u64, gates: u32, food: i32) -> Submission {
+        let mut sequence = GateSequence::new(seed);
+        let mut replay = vec![ReplaySample {
+            time: 0.0,
+            x: 0.0,
+            y: 0.0,
+            score: 0,
+            gates: 0,
+        }];
+        for (gate, layout) in (1..=gates).zip(&mut sequence) {
+            let (left, right) = layout.gap();
+            let previous = *replay.last().unwrap();
+            for step in 1..=STEPS_PER_SECOND {
+                let t = step as f32 / STEPS_PER_SECOND as f32;
+                let passed = step == STEPS_PER_SECOND;
+                replay.push(ReplaySample {
+                    time: previous.time + t,
+                    x: previous.x + ((left + right) / 2.0 - previous.x) * t,
+                    y: previous.y + (layout.y - previous.y) * t,
+                    score: if passed {
+                        gate as i32 + food * gate as i32 / gates as i32
+                    } else {
+                        previous.score
+                    },
+                    gates: if passed { gate } else { previous.gates },
+                });
+            }
+        }
+        Submission {
+            name: "Test".to_string(),
+            score: replay.last().unwrap().score,
+            seed,
+            replay,
+        }
+    }
+
+    #[test]
+    fn accepts_a_real_run() {
+        assert_eq!(verify(&replay(42, 10, 8)), Ok(()));
+    }
+
+    #[test]
+    fn rejects_inflated_food_score() {
+        let mut submission = replay(42, 10, 8);
+        submission.replay.push(ReplaySample {
+            time: 10.05,
+            score: 1_000_000,
+            ..*submission.replay.last().unwrap()
+        });
+        submission.score = 1_000_000;
+        assert!(verify(&submission).is_err());
+
+        let submission = Submission {
+            name: "Test".to_string(),
+            score: 1_000_000,
+            seed: 1,
+            replay: vec![
+                ReplaySample {
+                    time: 0.0,
+                    x: 0.0,
+                    y: 0.0,
+                    score: 0,
+                    gates: 0,
+                },
+                ReplaySample {
+                    time: 1.0,
+                    x: 0.0,
+                    y: 0.0,
+                    score: 1_000_000,
+                    gates: 0,
+                },
+            ],
+        };
+        assert!(verify(&submission).is_err());
+    }
+
+    #[test]
+    fn rejects_missed_gates() {
+        let mut submission = replay(42, 10, 0);
+        for sample in submission.replay.iter_mut() {
+            // far outside of every gap
+            sample.x = LEVEL_WIDTH / 2.0;
+        }
+        assert!(verify(&submission).is_err());
+    }
+
+    #[test]
+    fn rejects_forged_replay() {
+        let forged = |replay: Vec<ReplaySample>| Submission {
+            name: "Test".to_string(),
+            score: replay.last().unwrap().score,
+            seed: 42,
+            replay,
+        };
+        let sample = |time, x, y, score| ReplaySample {
+            time,
+            x,
+            y,
+            score,
+            gates: score as u32,
+        };
+        // one jump from the bottom left to far above the level
+        let jump = vec![sample(0.0, -360.0, 0.0, 0), sample(1.0, 360.0, 1e7, 5000)];
+        assert!(verify(&forged(jump)).is_err());
+        // the same with samples close enough together, it is faster than the view
+        let climb: Vec<ReplaySample> = (0..=20)
+            .map(|step| sample(step as f32 * 0.05, 0.0, step as f32 * 5e5, step * 250))
+            .collect();
+        assert!(verify(&forged(climb)).is_err());
+    }
+
+    #[test]
+    fn rejects_path_through_a_wall() {
+        let mut submission = replay(42, 3, 0);
+        let gate = GateSequence::new(42).next().unwrap();
+        let (left, right) = gate.gap();
+        // straight up at the same distance from the gap, on the side with more wall
+        let x = if left + right > 0.0 {
+            -LEVEL_WIDTH / 2.0
+        } else {
+            LEVEL_WIDTH / 2.0
+        };
+        for sample in submission.replay.iter_mut() {
+            if sample.y <= gate.y {
+                sample.x = x;
+            }
+        }
+        let error = verify(&submission).err().unwrap();
+        assert!(error.contains("wall"), "{}", error);
+    }
+
+    #[test]
+    fn gates_only_depend_on_the_seed() {
+        let gates: Vec<GateLayout> = GateSequence::new(7).take(20).collect();
+        assert_eq!(gates, GateSequence::new(7).take(20).collect::<Vec<_>>());
+        for (lower, upper) in gates.iter().zip(gates.iter().skip(1)) {
+            assert!(upper.y - lower.y >= MIN_GATE_SPACING - GATE_Y_RANGE);
+        }
+    }
+
+    #[test]
+    fn messages_survive_a_round_trip() {
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, "POST /scores HTTP/1.1", "{\"a\":1}").unwrap();
+        let message = read_message(&mut buffer.as_slice()).unwrap();
+        assert_eq!(message.start_line, "POST /scores HTTP/1.1");
+        assert_eq!(message.target(), "/scores");
+        assert_eq!(message.body, "{\"a\":1}");
+    }
+
+    #[test]
+    fn refuses_huge_bodies() {
+        let request = "POST /scores HTTP/1.1\r\nContent-Length: 99999999999\r\n\r\n";
+        let error = read_message(&mut request.as_bytes()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -3,35 +3,140 @@
 // silence unused wanrings while learning
 use bevy::sprite::collide_aabb::collide;
 use bevy::{prelude::*, render::camera::*, window::*};
+use audio::SfxChannels;
+use background::Star;
+use camera::{CameraState, LetterboxBar};
+use controls::{Controller, DragGesture, PlayerInput};
+use game_assets::GameAssets;
+use leaderboard::{Leaderboard, LeaderboardText};
+use ghost::Ghosts;
+use lanes::{GameMode, Lane, LaneView, LaneViews};
+use leaderboard_protocol::{GateLayout, GateSequence, GAP_WIDTH};
+use particles::Particle;
+use progress::Progress;
 use rand::Rng;
-// use bevy_kira_audio::{Audio, AudioPlugin, AudioChannel};
+use settings::Settings;
+use snapshot::DeathSnapshot;
+use stats::{DeathCause, RunStats};
+use stamina::Stamina;
+use std::cmp::Ordering;
+use theme::{Theme, ThemeText};
+use trail::TrailSegment;
+use versus::{FixedStep, Network, VersusMatch, VersusResultText};
+use bevy_kira_audio::AudioPlugin;
+
+mod achievements;
+mod art;
+mod audio;
+mod background;
+mod bot;
+mod camera;
+mod colorblind;
+mod controls;
+mod coop;
+mod game_assets;
+mod ghost;
+mod lanes;
+mod leaderboard;
+mod leaderboard_protocol;
+mod menu;
+mod music;
+mod particles;
+mod progress;
+mod settings;
+mod skins;
+mod snapshot;
+mod sounds;
+mod stamina;
+mod stats;
+mod storage;
+mod theme;
+mod trail;
+mod versus;
 
 const VEL_CLIPPING: f32 = 1e-3;
 const PLAYER_SPEED: f32 = 8.0;
-const CAMERA_SPEED: f32 = 128.0;
+// walls are spawned when they are this close above the view, in world units
+const SPAWN_AHEAD: f32 = 48.0;
+// the play area in world units, it is scaled to fit into the window and the rest is letterboxed
+const PLAY_WIDTH: f32 = 720.0;
+const PLAY_HEIGHT: f32 = 760.0;
+// sqrt((PLAY_WIDTH^2 + PLAY_HEIGHT^2) / 2), sizes of the entities are relative to it
+const PLAY_DIAGONAL_WEIGHTED: f32 = 740.27;
+// time between crashing and the deathscreen
+const DEATH_DELAY: f32 = 0.6;
+// camera trauma when bumping into the edge of the view at full speed
+const BUMP_TRAUMA: f32 = 0.5;
+const DEATH_TRAUMA: f32 = 0.8;
+// extra score for every player when all live players of a lane passed the same gate
+const TEAM_BONUS: i32 = 2;
 
 // components
 struct Player;
+// the number of the player, 0 is the first player
+struct PlayerIndex(usize);
 struct Health(f32);
 struct Velocity(Vec3);
 struct Score(i32);
 struct Food;
-struct FoodEatenEvent(Entity);
-struct GatePassedEvent(Entity);
-struct PlayerLostEvent;
+// the player crashed, the other players keep racing
+struct Crashed;
+struct FoodEatenEvent {
+    food: Entity,
+    player: Entity,
+}
+struct GatePassedEvent {
+    gate: Entity,
+    player: Entity,
+}
+// the drag gesture of the player was released, impulse is the change of its velocity
+struct PlayerPushedEvent {
+    player: Entity,
+    impulse: Vec2,
+}
+// the player that crashed, the entity that was hit, if any, and where the player was.
+// player_lost sends one per player
+#[derive(Clone, Copy)]
+struct PlayerLostEvent {
+    player: Entity,
+    cause: DeathCause,
+    entity: Option<Entity>,
+    position: Vec2,
+}
+// sent by the collision checks, several of them can report the same player in a frame
+struct CrashReport(PlayerLostEvent);
 struct Wall;
 struct Scoreboard;
 struct Gate;
-struct GestureLine;
+// the players that passed the gate
+struct GatePasses(Vec<Entity>);
+// the player it belongs to
+struct GestureLine(Entity);
+// on the lane camera, every lane spawns its walls from its own generator with the same seed
+struct WallSpawner {
+    gates: GateSequence,
+    next: GateLayout,
+}
 struct PauseMenuText;
-
-struct ButtonMaterials {
-    normal: Handle<ColorMaterial>,
-    hovered: Handle<ColorMaterial>,
-    pressed: Handle<ColorMaterial>,
+// font size of ui text relative to the weighted window diagonal
+struct UiFontSize(f32);
+// what happens when a button is clicked
+enum ButtonAction {
+    Play,
+    // split screen race
+    Race,
+    // two players in one lane
+    Coop,
+    // against another instance over the network
+    Versus,
+    // plays the same mode again
+    Respawn,
+    Menu,
+    Achievements,
+    Stats,
+    PreviousSkin,
+    NextSkin,
 }
-struct ScoreSound(Handle<AudioSource>);
-struct DeathSound(Handle<AudioSource>);
 
 
 // Resources
@@ -40,44 +145,166 @@ struct WindowSize {
     height: f32,
 }
 struct WindowSizeDiagonalWeighted(f32);
-struct LastWallSpawnedAt(f64);
-struct DragGesture {
-    start_pos: Vec2,
-    is_dragging: bool,
-}
+struct DeathTimer(Timer);
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 enum AppState {
+    Loading,
+    Menu,
     Playing,
+    Dying,
     Dead,
-    Paused
+    Paused,
+    Achievements,
+    Stats,
+    // waiting for the other instance in the versus mode
+    Connecting,
 }
 
 
 fn setup(
     mut commands: Commands,
     mut windows: ResMut<Windows>,
-    time: Res<Time>,
+    game_assets: Res<GameAssets>,
+    settings: Res<Settings>,
+    theme: Res<Theme>,
+    mode: Res<GameMode>,
+    ghosts: Res<Ghosts>,
+    versus_match: Option<Res<VersusMatch>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    asset_server: Res<AssetServer>,
 ) {
-    // camera
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-
-    commands.insert_resource(DragGesture {
-        start_pos: Vec2::ZERO,
-        is_dragging: false,
-    });
-
     let window = windows.get_primary_mut().unwrap();
     let width = window.width();
     let height = window.height();
-    let window_size_diagonal_weighted: f32 = ((width * width + height * height) / 2.0).sqrt();
-    // save window size
-    commands.insert_resource(WindowSize {
+    let window_size = WindowSize {
         width: width,
         height: height,
-    });
+    };
+
+    // every lane gets the same walls, a ghost run and a versus match bring their own
+    let versus_seed = versus_match
+        .filter(|_| *mode == GameMode::Versus)
+        .map(|versus_match| versus_match.seed);
+    let seed = ghosts
+        .seed(*mode)
+        .or(versus_seed)
+        .unwrap_or_else(|| rand::thread_rng().gen::<u64>());
+    for index in 0..mode.lanes() {
+        let lane = Lane(index);
+
+        // camera
+        let mut camera = OrthographicCameraBundle::new_2d();
+        if let Some(name) = lanes::camera_name(&lane) {
+            camera.camera.name = Some(name);
+        }
+        let view_scale = lanes::lane_scale(&window_size, *mode, 1.0);
+        camera.transform.scale = Vec3::new(view_scale, view_scale, 1.0);
+        camera.transform.translation.x = lanes::camera_x(&lane, *mode, &window_size, view_scale);
+        commands
+            .spawn_bundle(camera)
+            .insert(CameraState::default())
+            .insert({
+                let mut gates = GateSequence::new(seed);
+                let next = gates.next().unwrap();
+                WallSpawner { gates, next }
+            })
+            .insert(lane);
+        camera::spawn_letterbox(&mut commands, &game_assets, lane);
+
+        // spawn players, the first one uses the mouse unless the autopilot plays it
+        let players_per_lane = mode.players_per_lane();
+        for slot in 0..players_per_lane {
+            let player_index = index * players_per_lane + slot;
+            let material = if player_index == 0 {
+                game_assets.player_material.clone()
+            } else {
+                game_assets.player_two_material.clone()
+            };
+            let controller = match bot::skill_of(&settings, player_index) {
+                Some(skill) => Controller::Bot(skill),
+                None if player_index == 0 => Controller::Mouse(controls::gamepad_of(player_index)),
+                None => Controller::Keys(controls::gamepad_of(player_index)),
+            };
+            // players of the same lane start next to each other
+            let x = lane.x()
+                + (slot as f32 - (players_per_lane - 1) as f32 / 2.0) * PLAY_WIDTH / 4.0;
+            let player = commands
+                .spawn_bundle(SpriteBundle {
+                    material,
+                    sprite: Sprite::new(Vec2::new(
+                        0.03 * PLAY_DIAGONAL_WEIGHTED,
+                        0.03 * PLAY_DIAGONAL_WEIGHTED,
+                    )),
+                    transform: Transform {
+                        translation: Vec3::new(x, 0.0, 1.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(Player)
+                .insert(PlayerIndex(player_index))
+                .insert(Health(0.0))
+                .insert(Score(0))
+                .insert(Velocity(Vec3::ZERO))
+                .insert(controller)
+                .insert(DragGesture::default())
+                .insert(Stamina::new(settings.stamina))
+                .insert(lane)
+                .id();
+            if let Controller::Bot(_) = controller {
+                commands.entity(player).insert(bot::Autopilot::default());
+            }
+            // the other side of a versus match simulates the player in the same ticks
+            if *mode == GameMode::Versus {
+                commands.entity(player).insert(FixedStep);
+            }
+            if settings.stamina {
+                stamina::spawn_stamina_bar(&mut commands, &game_assets, player, lane, slot);
+            }
+            trail::spawn_trail(&mut commands, &mut materials, player);
+        }
+
+        spawn_food(
+            &mut commands,
+            &game_assets,
+            lane,
+            Vec2::new(lane.x() + PLAY_WIDTH / 4.0, PLAY_HEIGHT / 4.0),
+        );
+
+        // spawn_scoreboard(commands, window_size_diagonal_weighted, asset_server);
+        // spawn scoreboard
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text {
+                    alignment: TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        vertical: VerticalAlign::Top,
+                    },
+                    sections: vec![TextSection {
+                        value: "Score".to_string(),
+                        style: TextStyle {
+                            font_size: PLAY_DIAGONAL_WEIGHTED * 0.08,
+                            font: game_assets.font_medium.clone(),
+                            color: theme.text,
+                        },
+                    }],
+                },
+                transform: Transform {
+                    translation: Vec3::new(lane.x(), PLAY_HEIGHT / 2.5, 2.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Scoreboard)
+            .insert(ThemeText::Primary)
+            .insert(lane);
+    }
+    ghost::spawn_ghost(&mut commands, &game_assets, &theme, &ghosts, *mode, seed);
+    commands.insert_resource(LaneViews::new(*mode));
+
+    let window_size_diagonal_weighted: f32 = ((width * width + height * height) / 2.0).sqrt();
+    // save window size
+    commands.insert_resource(window_size);
     commands.insert_resource(WindowSizeDiagonalWeighted(window_size_diagonal_weighted));
     // println!("Diagonal: {}", window_size_diagonal_weighted);
     // position window
@@ -87,130 +314,99 @@ fn setup(
     //     (1080 - height as i32) / 2,
     // ));
 
-    commands.insert_resource(LastWallSpawnedAt(time.seconds_since_startup()));
+    commands.insert_resource(RunStats::default());
 
-    // spawn player
-    commands
-        .spawn_bundle(SpriteBundle {
-            material: materials.add(Color::rgb_u8(235, 107, 52).into()),
-            sprite: Sprite::new(Vec2::new(
-                0.03 * window_size_diagonal_weighted,
-                0.03 * window_size_diagonal_weighted,
-            )),
-            transform: Transform {
-                translation: Vec3::new(0.0, 0.0, 1.0),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Player)
-        .insert(Health(0.0))
-        .insert(Score(0))
-        .insert(Velocity(Vec3::ZERO));
+    particles::spawn_particle_pool(&mut commands, &mut materials);
+}
 
-    // spawn food
+fn spawn_food(
+    commands: &mut Commands,
+    game_assets: &GameAssets,
+    lane: Lane,
+    position: Vec2,
+) {
     commands
         .spawn_bundle(SpriteBundle {
-            material: materials.add(Color::rgb_u8(52, 235, 140).into()),
+            material: game_assets.food_material.clone(),
             sprite: Sprite::new(Vec2::new(
-                0.025 * window_size_diagonal_weighted,
-                0.025 * window_size_diagonal_weighted,
+                0.025 * PLAY_DIAGONAL_WEIGHTED,
+                0.025 * PLAY_DIAGONAL_WEIGHTED,
             )),
             transform: Transform {
-                translation: Vec3::new(width / 4.0, height / 4.0, 0.0),
+                translation: position.extend(0.0),
                 ..Default::default()
             },
             ..Default::default()
         })
-        .insert(Food);
-
-    // spawn_scoreboard(commands, window_size_diagonal_weighted, asset_server);
-    // spawn scoreboard
-    commands
-        .spawn_bundle(Text2dBundle {
-            text: Text {
-                alignment: TextAlignment {
-                    horizontal: HorizontalAlign::Center,
-                    vertical: VerticalAlign::Top,
-                },
-                sections: vec![TextSection {
-                    value: "Score".to_string(),
-                    style: TextStyle {
-                        font_size: window_size_diagonal_weighted * 0.08,
-                        font: asset_server.load("fonts/BaiJamjuree-Medium.ttf"),
-                        color: Color::rgb(1.0, 1.0, 1.0),
-                    },
-                }],
-            },
-            transform: Transform {
-                translation: Vec3::new(0., height / 2.5, 2.),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Scoreboard);
-
-    // set button materials
-    commands.insert_resource(ButtonMaterials {
-        normal: materials.add(Color::rgba_u8(0x37, 0x41, 0x51, 255).into()),
-        hovered: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.25).into()),
-        pressed: materials.add(Color::rgba_u8(0x37, 0x41, 0x51, 10).into()),
-    });
+        .insert(Food)
+        .insert(lane);
+}
 
-    // score sound: pickupCoin
-    commands.insert_resource(ScoreSound(
-        asset_server.load("sounds/pickupCoin.wav").into(),
-    ));
-    commands.insert_resource(DeathSound(asset_server.load("sounds/death.wav").into()));
+// somewhere just above the view of the lane
+fn food_spawn_position(lane: &Lane, view: &LaneView, rng: &mut impl Rng) -> Vec2 {
+    Vec2::new(
+        lane.x() + rng.gen_range(-PLAY_WIDTH / 2.0..PLAY_WIDTH / 2.0),
+        rng.gen_range(
+            view.half_height + view.camera_y..view.half_height + view.camera_y + 32.0,
+        ),
+    )
 }
 
 // needs to be called every time because the camera always changes!
 fn update_scoreboard(
-    mut query_set: QuerySet<(
-        Query<(&mut Transform, &mut Text, With<Scoreboard>)>,
-        Query<(&Transform, With<OrthographicProjection>)>,
-        Query<&Score, With<Player>>,
-    )>,
-    window_size: Res<WindowSize>,
+    lane_views: Res<LaneViews>,
+    player_query: Query<(&Score, &PlayerIndex, &Lane), With<Player>>,
+    mut scoreboard_query: Query<(&mut Transform, &mut Text, &Lane), With<Scoreboard>>,
 ) {
-    let mut score = 0;
-    if let Ok(s) = query_set.q2_mut().single_mut() {
-        score = s.0;
-    }
-    let mut camera_y = 0.0f32;
-    if let Ok((tf, _)) = query_set.q1_mut().single_mut() {
-        camera_y = tf.translation.y;
-    }
-    if let Ok((mut tf, mut text, _)) = query_set.q0_mut().single_mut() {
+    for (mut tf, mut text, lane) in scoreboard_query.iter_mut() {
+        let mut scores: Vec<(usize, i32)> = player_query
+            .iter()
+            .filter(|(_, _, player_lane)| *player_lane == lane)
+            .map(|(score, player_index, _)| (player_index.0, score.0))
+            .collect();
+        scores.sort_unstable();
         if let Some(section) = text.sections.get_mut(0) {
-            section.value = format!("Score {}", score).to_string();
+            section.value = match scores.as_slice() {
+                [(_, score)] => format!("Score {}", score).to_string(),
+                // players sharing a lane
+                _ => scores
+                    .iter()
+                    .map(|(player_index, score)| format!("P{} {}", player_index + 1, score))
+                    .collect::<Vec<_>>()
+                    .join("  "),
+            };
         }
-        tf.translation.y = camera_y - window_size.height / 2.5;
+        tf.translation.y = lane_views.get(lane).camera_y - PLAY_HEIGHT / 2.5;
     }
 }
 
 fn player_check_food(
-    mut query_set: QuerySet<(
-        Query<(&Transform, &Sprite, With<Player>)>,
-        Query<(Entity, &Transform, &Sprite, With<Food>)>,
-    )>,
+    player_query: Query<(Entity, &Transform, &Sprite, &Lane), (With<Player>, Without<Crashed>)>,
+    food_query: Query<(Entity, &Transform, &Sprite, &Lane), With<Food>>,
     mut ev_food_eaten: EventWriter<FoodEatenEvent>,
 ) {
-    let mut player_translation: Vec3 = Vec3::ZERO;
-    let mut food_translation: Vec3 = Vec3::new(50., 50., 50.);
-    let mut player_size = Vec2::ZERO;
-    if let Ok((player_tf, sprite, _)) = query_set.q0_mut().single_mut() {
-        player_translation = player_tf.translation;
-        player_size = sprite.size;
-    }
-    for (food_entity, food_tf, sprite, __) in query_set.q1_mut().iter_mut() {
-        food_translation = food_tf.translation;
-        if (player_translation.x - food_translation.x).abs() < (player_size.x + sprite.size.x) / 2.0
-            && (player_translation.y - food_translation.y).abs()
-                < (player_size.y + sprite.size.y) / 2.0
-        {
-            ev_food_eaten.send(FoodEatenEvent(food_entity));
-            break;
+    // food can only be eaten once
+    let mut eaten = Vec::new();
+    for (player, player_tf, player_sprite, player_lane) in player_query.iter() {
+        let player_translation = player_tf.translation;
+        let player_size = player_sprite.size;
+        for (food_entity, food_tf, sprite, food_lane) in food_query.iter() {
+            if food_lane != player_lane || eaten.contains(&food_entity) {
+                continue;
+            }
+            let food_translation = food_tf.translation;
+            if (player_translation.x - food_translation.x).abs()
+                < (player_size.x + sprite.size.x) / 2.0
+                && (player_translation.y - food_translation.y).abs()
+                    < (player_size.y + sprite.size.y) / 2.0
+            {
+                eaten.push(food_entity);
+                ev_food_eaten.send(FoodEatenEvent {
+                    food: food_entity,
+                    player,
+                });
+                break;
+            }
         }
     }
     // for ((mut transform, entity_type)) in query.iter_mut() {
@@ -222,276 +418,266 @@ fn player_check_food(
 
 fn food_eaten(
     mut ev_food_eaten: EventReader<FoodEatenEvent>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut query_set: QuerySet<(
-        Query<(&mut Score, With<Player>)>,
-        Query<(&Transform, With<OrthographicProjection>)>,
-    )>,
+    game_assets: Res<GameAssets>,
+    mut player_query: Query<(&mut Score, &Lane), With<Player>>,
     mut commands: Commands,
-    window_size: Res<WindowSize>,
-    window_size_diag: Res<WindowSizeDiagonalWeighted>,
+    lane_views: Res<LaneViews>,
 ) {
-    let mut camera_y = 0.0;
-    if let Ok((camera_tf, _)) = query_set.q1_mut().single_mut() {
-        camera_y = camera_tf.translation.y;
-    }
     for ev in ev_food_eaten.iter() {
-        commands.entity(ev.0).despawn();
-        if let Ok((mut player_score, _)) = query_set.q0_mut().single_mut() {
+        commands.entity(ev.food).despawn();
+        if let Ok((mut player_score, lane)) = player_query.get_mut(ev.player) {
             player_score.0 += 1;
             // println!("Score: {}", player_score.0);
 
             // update UI
+            let view = lane_views.get(lane);
+            let mut rng = rand::thread_rng();
+            // duplicate food
+            for _i in 0..2 {
+                let position = food_spawn_position(lane, &view, &mut rng);
+                spawn_food(&mut commands, &game_assets, *lane, position);
+            }
         }
-        let mut rng = rand::thread_rng();
-        // duplicate food
-        for _i in 0..2 {
-            commands
-                .spawn_bundle(SpriteBundle {
-                    material: materials.add(Color::rgb_u8(52, 235, 140).into()),
-                    sprite: Sprite::new(Vec2::new(
-                        0.025 * window_size_diag.0,
-                        0.025 * window_size_diag.0,
-                    )),
-                    transform: Transform {
-                        translation: Vec3::new(
-                            rng.gen_range(-window_size.width / 2.0..window_size.width / 2.0),
-                            rng.gen_range(
-                                window_size.height / 2.0 + camera_y
-                                    ..window_size.height / 2.0 + camera_y + 32.0,
-                            ),
-                            0.,
-                        ),
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                })
-                .insert(Food);
-        }
-    }
-}
-
-// moves the camera upwards all the time
-fn camera_movement(
-    mut query_set: QuerySet<(
-        Query<(&mut Transform, With<OrthographicProjection>)>,
-        Query<(&Score, With<Player>)>,
-    )>,
-    window_size_diag: Res<WindowSizeDiagonalWeighted>,
-    time: Res<Time>,
-) {
-    let mut score = 0;
-    if let Ok((player_score, _)) = query_set.q1_mut().single_mut() {
-        score = player_score.0;
-    }
-    if let Ok((mut transform, _)) = query_set.q0_mut().single_mut() {
-        transform.translation.y +=
-            time.delta_seconds() * CAMERA_SPEED * (1.0 + 0.025 * score as f32) * window_size_diag.0
-                / 720.0;
     }
 }
 
 // despawn all entities that are out of range for better performance
 fn handle_entities_out_of_range(
-    window_size: Res<WindowSize>,
     mut commands: Commands,
-    mut query_set: QuerySet<(
-        Query<(Entity, &Transform, &Sprite), Without<Food>>,
-        Query<(&Transform, With<OrthographicProjection>)>,
-        Query<(Entity, &Transform, &Sprite), With<Food>>,
-    )>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    window_size_diag: Res<WindowSizeDiagonalWeighted>,
+    query: Query<
+        (Entity, &Transform, &Sprite, &Lane),
+        (
+            Without<Food>,
+            Without<LetterboxBar>,
+            Without<Particle>,
+            Without<TrailSegment>,
+            Without<Star>,
+            Without<Player>,
+        ),
+    >,
+    food_query: Query<(Entity, &Transform, &Sprite, &Lane), With<Food>>,
+    game_assets: Res<GameAssets>,
+    lane_views: Res<LaneViews>,
 ) {
-    let mut camera_y = 0.0;
-    if let Ok((camera_tf, _)) = query_set.q1_mut().single_mut() {
-        camera_y = camera_tf.translation.y;
-    }
-    for (entity, transform, sprite) in query_set.q0_mut().iter_mut() {
-        if transform.translation.y + sprite.size.y / 2.0 < camera_y - window_size.height / 2.0 {
+    let is_below = |transform: &Transform, sprite: &Sprite, lane: &Lane| {
+        let view = lane_views.get(lane);
+        transform.translation.y + sprite.size.y / 2.0 < view.camera_y - view.half_height
+    };
+    for (entity, transform, sprite, lane) in query.iter() {
+        if is_below(transform, sprite, lane) {
             commands.entity(entity).despawn();
         }
     }
-    let mut num_food = 0;
-    for (entity, transform, sprite) in query_set.q2_mut().iter_mut() {
-        num_food += 1;
-        if transform.translation.y + sprite.size.y / 2.0 < camera_y - window_size.height / 2.0 {
+    let mut num_food = vec![0; lane_views.0.len()];
+    for (entity, transform, sprite, lane) in food_query.iter() {
+        if let Some(count) = num_food.get_mut(lane.0) {
+            *count += 1;
+        }
+        if is_below(transform, sprite, lane) {
             commands.entity(entity).despawn();
         }
     }
-    if num_food == 0 {
-        // spawn new food
-        let mut rng = rand::thread_rng();
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: materials.add(Color::rgb_u8(52, 235, 140).into()),
-                sprite: Sprite::new(Vec2::new(
-                    0.025 * window_size_diag.0,
-                    0.025 * window_size_diag.0,
-                )),
-                transform: Transform {
-                    translation: Vec3::new(
-                        rng.gen_range(-window_size.width / 2.0..window_size.width / 2.0),
-                        rng.gen_range(
-                            window_size.height / 2.0 + camera_y
-                                ..window_size.height / 2.0 + camera_y + 32.0,
-                        ),
-                        0.,
-                    ),
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(Food);
+    for (index, num_food) in num_food.iter().enumerate() {
+        if *num_food == 0 {
+            // spawn new food
+            let lane = Lane(index);
+            let mut rng = rand::thread_rng();
+            let position = food_spawn_position(&lane, &lane_views.get(&lane), &mut rng);
+            spawn_food(&mut commands, &game_assets, lane, position);
+        }
     }
 }
 
 fn player_wall_collision(
-    mut query_set: QuerySet<(
-        Query<(&Transform, &Sprite, With<Wall>)>,
-        Query<(&Transform, &Sprite, With<Player>)>,
-    )>,
-    mut player_lost_event: EventWriter<PlayerLostEvent>,
+    player_query: Query<(Entity, &Transform, &Sprite, &Lane), (With<Player>, Without<Crashed>)>,
+    wall_query: Query<(Entity, &Transform, &Sprite, &Lane), With<Wall>>,
+    mut crash_reports: EventWriter<CrashReport>,
 ) {
-    let mut player_pos = Vec3::ZERO;
-    let mut player_size = Vec2::ZERO;
-    if let Ok((tf, sprite, _)) = query_set.q1_mut().single_mut() {
-        player_pos = tf.translation;
-        player_size = sprite.size;
-    }
-    for (wall_tf, wall_sprite, __) in query_set.q0_mut().iter_mut() {
-        if collide(
-            player_pos,
-            player_size,
-            wall_tf.translation,
-            wall_sprite.size,
-        )
-        .is_some()
-        {
-            // println!("Lost!");
-            player_lost_event.send(PlayerLostEvent);
+    for (player, tf, sprite, player_lane) in player_query.iter() {
+        let player_pos = tf.translation;
+        let player_size = sprite.size;
+        for (entity, wall_tf, wall_sprite, wall_lane) in wall_query.iter() {
+            if wall_lane == player_lane
+                && collide(
+                    player_pos,
+                    player_size,
+                    wall_tf.translation,
+                    wall_sprite.size,
+                )
+                .is_some()
+            {
+                // println!("Lost!");
+                crash_reports.send(CrashReport(PlayerLostEvent {
+                    player,
+                    cause: DeathCause::Wall,
+                    entity: Some(entity),
+                    position: player_pos.truncate(),
+                }));
+                break;
+            }
         }
     }
 }
 
 fn player_gate_collision(
-    mut query_set: QuerySet<(
-        Query<(&Transform, &Sprite, With<Player>)>,
-        Query<(Entity, &Transform, &Sprite, With<Gate>)>,
-    )>,
+    player_query: Query<(Entity, &Transform, &Sprite, &Lane), (With<Player>, Without<Crashed>)>,
+    gate_query: Query<(Entity, &Transform, &Sprite, &Lane, &GatePasses), With<Gate>>,
     mut gate_passed_event: EventWriter<GatePassedEvent>,
 ) {
-    let mut player_pos = Vec3::ZERO;
-    let mut player_size = Vec2::ZERO;
-    if let Ok((tf, sprite, _)) = query_set.q0_mut().single_mut() {
-        player_pos = tf.translation;
-        player_size = sprite.size;
-    }
-
-    for (entity, gate_tf, gate_sprite, _) in query_set.q1_mut().iter_mut() {
-        if collide(
-            player_pos,
-            player_size,
-            gate_tf.translation,
-            gate_sprite.size,
-        )
-        .is_some()
-        {
-            gate_passed_event.send(GatePassedEvent(entity));
+    for (player, tf, sprite, player_lane) in player_query.iter() {
+        let player_pos = tf.translation;
+        let player_size = sprite.size;
+        for (entity, gate_tf, gate_sprite, gate_lane, passes) in gate_query.iter() {
+            if gate_lane == player_lane
+                && !passes.0.contains(&player)
+                && collide(
+                    player_pos,
+                    player_size,
+                    gate_tf.translation,
+                    gate_sprite.size,
+                )
+                .is_some()
+            {
+                gate_passed_event.send(GatePassedEvent {
+                    gate: entity,
+                    player,
+                });
+            }
         }
     }
 }
 
+
+// the gate stays until every live player of its lane passed it, then they all get the team bonus
 fn gate_passed(
     mut commands: Commands,
     mut gate_passed_event: EventReader<GatePassedEvent>,
-    mut player_query: Query<(&mut Score, With<Player>)>,
+    mut player_query: Query<(&mut Score, &Lane), With<Player>>,
+    live_query: Query<(Entity, &Lane), (With<Player>, Without<Crashed>)>,
+    mut gate_query: Query<&mut GatePasses>,
 ) {
     for ev in gate_passed_event.iter() {
-        if let Ok((mut player_score, _)) = player_query.single_mut() {
-            player_score.0 += 1;
-            // println!("Score: {}", player_score.0);
-            commands.entity(ev.0).despawn();
+        let mut passes = match gate_query.get_mut(ev.gate) {
+            Ok(passes) => passes,
+            Err(_) => continue,
+        };
+        if passes.0.contains(&ev.player) {
+            continue;
+        }
+        let lane = match player_query.get_mut(ev.player) {
+            Ok((mut player_score, lane)) => {
+                player_score.0 += 1;
+                // println!("Score: {}", player_score.0);
+                *lane
+            }
+            Err(_) => continue,
+        };
+        passes.0.push(ev.player);
+        let all_passed = live_query
+            .iter()
+            .filter(|(_, player_lane)| **player_lane == lane)
+            .all(|(player, _)| passes.0.contains(&player));
+        if all_passed {
+            if passes.0.len() > 1 {
+                for player in passes.0.iter() {
+                    if let Ok((mut player_score, _)) = player_query.get_mut(*player) {
+                        player_score.0 += TEAM_BONUS;
+                    }
+                }
+            }
+            commands.entity(ev.gate).despawn();
         }
     }
 }
 
-// checks if the player leaves the view
+// checks if the players leave the view of their lane
 fn player_check_leave_view(
-    mut query_set: QuerySet<(
-        Query<&Transform, With<OrthographicProjection>>,
-        Query<(&mut Transform, &mut Velocity, &Sprite), With<Player>>,
-    )>,
-    window_size: Res<WindowSize>,
-    mut player_lost_event: EventWriter<PlayerLostEvent>,
+    lane_views: Res<LaneViews>,
+    mut player_query: Query<
+        (Entity, &mut Transform, &mut Velocity, &Sprite, &Lane),
+        (With<Player>, Without<Crashed>),
+    >,
+    mut camera_query: Query<(&mut CameraState, &Lane)>,
+    mut crash_reports: EventWriter<CrashReport>,
 ) {
-    let mut camera_y = 0.0;
-    if let Ok(camera_tf) = query_set.q0_mut().single_mut() {
-        camera_y = camera_tf.translation.y;
-    }
-    if let Ok((mut tf, mut velocity, sprite)) = query_set.q1_mut().single_mut() {
-        if tf.translation.x - sprite.size.x/2.0 < -window_size.width/2.0 {
+    // trauma for the camera of a lane
+    let mut bumps = Vec::new();
+    for (player, mut tf, mut velocity, sprite, lane) in player_query.iter_mut() {
+        let view = lane_views.get(lane);
+        let camera_y = view.camera_y;
+        let half_view_height = view.half_height;
+        let left = lane.x() - PLAY_WIDTH / 2.0;
+        let right = lane.x() + PLAY_WIDTH / 2.0;
+        let bump_trauma = BUMP_TRAUMA * (velocity.0.length() / PLAY_DIAGONAL_WEIGHTED).min(1.0);
+        if tf.translation.x - sprite.size.x/2.0 < left {
+            bumps.push((*lane, bump_trauma));
             velocity.0.x = 0.0;
             velocity.0.y = 0.0;
-            tf.translation.x = -window_size.width/2.0 + sprite.size.x/2.0;
+            tf.translation.x = left + sprite.size.x/2.0;
         }
-        else if tf.translation.x + sprite.size.x/2.0 > window_size.width/2.0 {
+        else if tf.translation.x + sprite.size.x/2.0 > right {
+            bumps.push((*lane, bump_trauma));
             velocity.0.x = 0.0;
             velocity.0.y = 0.0;
-            tf.translation.x = window_size.width/2.0 - sprite.size.x/2.0;
+            tf.translation.x = right - sprite.size.x/2.0;
         }
-        if tf.translation.y - sprite.size.y/2.0 < camera_y - window_size.height / 2.0 {
+        if tf.translation.y - sprite.size.y/2.0 < camera_y - half_view_height {
             // println!("Player lost!");
-            player_lost_event.send(PlayerLostEvent.into());
+            crash_reports.send(CrashReport(PlayerLostEvent {
+                player,
+                cause: DeathCause::FellBehind,
+                entity: None,
+                position: tf.translation.truncate(),
+            }));
         }
-        else if tf.translation.y + sprite.size.y/2.0 > camera_y + window_size.height / 2.0 {
+        else if tf.translation.y + sprite.size.y/2.0 > camera_y + half_view_height {
+            bumps.push((*lane, bump_trauma));
             velocity.0.x = 0.0;
             velocity.0.y = 0.0;
-            tf.translation.y = camera_y + window_size.height/2.0 - sprite.size.x/2.0;
+            tf.translation.y = camera_y + half_view_height - sprite.size.x/2.0;
+        }
+    }
+    for (mut camera_state, camera_lane) in camera_query.iter_mut() {
+        for (lane, trauma) in bumps.iter() {
+            if lane == camera_lane {
+                camera_state.add_trauma(*trauma);
+            }
         }
     }
 }
 
 fn spawn_walls(
-    window_size: Res<WindowSize>,
-    time: Res<Time>,
-    mut last_wall_spawned_at: ResMut<LastWallSpawnedAt>,
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut query_set: QuerySet<(
-        Query<(&mut Transform, With<OrthographicProjection>)>,
-        Query<(&mut Score, With<Player>)>,
-    )>,
-    window_size_diag: Res<WindowSizeDiagonalWeighted>,
+    game_assets: Res<GameAssets>,
+    lane_views: Res<LaneViews>,
+    mut spawner_query: Query<(&mut WallSpawner, &Lane)>,
+    player_query: Query<&Lane, (With<Player>, Without<Crashed>)>,
 ) {
-    let time_now = time.seconds_since_startup();
-    let mut score = 0;
-    if let Ok((player_score, _)) = query_set.q1_mut().single_mut() {
-        score = player_score.0;
-    }
-    if time_now - last_wall_spawned_at.0 > (2.0 - 0.1 * (score as f64)).max(1.0) {
-        last_wall_spawned_at.0 = time_now;
-        let mut rng = rand::thread_rng();
-        let gap_middle = rng.gen_range(-0.3f32..0.3f32);
-        let gap_width = 0.2f32;
-        let mut camera_y = 0.0;
-        if let Ok((camera_tf, _)) = query_set.q0_mut().single_mut() {
-            camera_y = camera_tf.translation.y;
-        }
-        let wall_y = rng.gen_range(
-            window_size.height / 2.0 + camera_y..window_size.height / 2.0 + camera_y + 32.0,
-        );
-        let gap_left = gap_middle - gap_width / 2.0;
-        let gap_right = gap_middle + gap_width / 2.0;
-        let wall_x_left = 0.5 * window_size.width * (-0.5 + gap_left);
-        let wall_x_right = 0.5 * window_size.width * (0.5 + gap_right);
-        let wall_width_left = window_size.width * (0.5 + gap_left);
-        let wall_width_right = window_size.width * (0.5 - gap_right);
-        let wall_height: f32 = window_size_diag.0 * 0.025;
-        // println!("gl{} gr{} xl{} xr{} wl{} wr{} wsum{}", gap_left, gap_right, wall_x_left/window_size.width, wall_x_right/window_size.width, wall_width_left, wall_width_right, wall_width_left+wall_width_right);
+    for (mut spawner, lane) in spawner_query.iter_mut() {
+        // the lane stops once its player crashed
+        if !player_query.iter().any(|player_lane| player_lane == lane) {
+            continue;
+        }
+        // the gates are fixed in the world, each one appears once it is about to come into view.
+        // the leaderboard server creates the same gates to verify runs
+        let view = lane_views.get(lane);
+        if spawner.next.y > view.camera_y + view.half_height + SPAWN_AHEAD {
+            continue;
+        }
+        let gate = spawner.next;
+        spawner.next = spawner.gates.next().unwrap();
+        let (gap_middle, wall_y) = (gate.gap_middle, gate.y);
+        let gap_left = gap_middle - GAP_WIDTH / 2.0;
+        let gap_right = gap_middle + GAP_WIDTH / 2.0;
+        let wall_x_left = lane.x() + 0.5 * PLAY_WIDTH * (-0.5 + gap_left);
+        let wall_x_right = lane.x() + 0.5 * PLAY_WIDTH * (0.5 + gap_right);
+        let wall_width_left = PLAY_WIDTH * (0.5 + gap_left);
+        let wall_width_right = PLAY_WIDTH * (0.5 - gap_right);
+        let wall_height: f32 = PLAY_DIAGONAL_WEIGHTED * 0.025;
+        // println!("gl{} gr{} xl{} xr{} wl{} wr{} wsum{}", gap_left, gap_right, wall_x_left/PLAY_WIDTH, wall_x_right/PLAY_WIDTH, wall_width_left, wall_width_right, wall_width_left+wall_width_right);
         commands
             .spawn_bundle(SpriteBundle {
-                material: materials.add(Color::rgb_u8(0xE5, 0xE7, 0xEB).into()),
+                material: game_assets.wall_material.clone(),
                 sprite: Sprite::new(Vec2::new(wall_width_left, wall_height)),
                 transform: Transform {
                     translation: Vec3::new(wall_x_left, wall_y, 0.1),
@@ -499,10 +685,11 @@ fn spawn_walls(
                 },
                 ..Default::default()
             })
-            .insert(Wall);
+            .insert(Wall)
+            .insert(*lane);
         commands
             .spawn_bundle(SpriteBundle {
-                material: materials.add(Color::rgb_u8(0xE5, 0xE7, 0xEB).into()),
+                material: game_assets.wall_material.clone(),
                 sprite: Sprite::new(Vec2::new(wall_width_right, wall_height)),
                 transform: Transform {
                     translation: Vec3::new(wall_x_right, wall_y, 0.1),
@@ -510,12 +697,13 @@ fn spawn_walls(
                 },
                 ..Default::default()
             })
-            .insert(Wall);
+            .insert(Wall)
+            .insert(*lane);
         commands
             .spawn_bundle(SpriteBundle {
-                material: materials.add(Color::rgba_u8(0x10, 0xB9, 0x81, 64).into()),
+                material: game_assets.gate_material.clone(),
                 sprite: Sprite::new(Vec2::new(
-                    window_size.width - wall_width_left - wall_width_right,
+                    PLAY_WIDTH - wall_width_left - wall_width_right,
                     wall_height,
                 )),
                 transform: Transform {
@@ -530,21 +718,86 @@ fn spawn_walls(
                 },
                 ..Default::default()
             })
-            .insert(Gate);
+            .insert(Gate)
+            .insert(GatePasses(Vec::new()))
+            .insert(*lane);
     }
 }
 
+// the crashed player stops, the run ends when nobody is left
 fn player_lost(
-    mut player_lost_event: EventReader<PlayerLostEvent>,
+    mut commands: Commands,
+    mut crash_reports: EventReader<CrashReport>,
+    mut player_lost_event: EventWriter<PlayerLostEvent>,
     mut app_state: ResMut<State<AppState>>,
-    audio: Res<Audio>,
-    death_sound: Res<DeathSound>,
+    game_assets: Res<GameAssets>,
+    lane_views: Res<LaneViews>,
+    mut player_query: Query<
+        (Entity, &mut Transform, &mut Velocity, &Lane),
+        (With<Player>, Without<Crashed>),
+    >,
+    mut camera_query: Query<(&mut CameraState, &Lane)>,
+    wall_query: Query<(&Transform, &Sprite), (With<Wall>, Without<Player>)>,
 ) {
-    for _ev in player_lost_event.iter() {
-        app_state.set(AppState::Dead.into());
-        audio.play(death_sound.0.clone());
+    // the players and the walls they hit
+    let mut crashed = Vec::new();
+    for CrashReport(ev) in crash_reports.iter() {
+        // the first report of a player counts
+        if crashed.iter().any(|(player, _)| *player == ev.player) {
+            continue;
+        }
+        if let Ok((_, _, mut velocity, lane)) = player_query.get_mut(ev.player) {
+            crashed.push((ev.player, ev.entity));
+            player_lost_event.send(*ev);
+            velocity.0 = Vec3::ZERO;
+            commands.entity(ev.player).insert(Crashed);
+            let lane = *lane;
+            for (mut camera_state, camera_lane) in camera_query.iter_mut() {
+                if *camera_lane == lane {
+                    camera_state.add_trauma(DEATH_TRAUMA);
+                }
+            }
+        }
+    }
+    if crashed.is_empty() {
         return;
     }
+    // lanes that still have a live player
+    let live_lanes: Vec<Lane> = player_query
+        .iter_mut()
+        .filter(|(entity, _, _, _)| !crashed.iter().any(|(player, _)| player == entity))
+        .map(|(_, _, _, lane)| *lane)
+        .collect();
+    // a teammate can still revive the player
+    for (player, hit) in crashed.iter() {
+        if let Ok((_, mut tf, _, lane)) = player_query.get_mut(*player) {
+            if live_lanes.contains(lane) {
+                let wall = hit
+                    .and_then(|entity| wall_query.get(entity).ok())
+                    .map(|(wall_tf, sprite)| (wall_tf.translation.truncate(), sprite.size));
+                let position =
+                    coop::marker_position(tf.translation.truncate(), &lane_views.get(lane), wall);
+                tf.translation.x = position.x;
+                tf.translation.y = position.y;
+                coop::spawn_revive_marker(&mut commands, &game_assets, *player, *lane, position);
+            }
+        }
+    }
+    if live_lanes.is_empty() {
+        // freeze the game for a moment before showing the deathscreen
+        app_state.set(AppState::Dying.into());
+        commands.insert_resource(DeathTimer(Timer::from_seconds(DEATH_DELAY, false)));
+    }
+}
+
+fn death_delay(
+    mut death_timer: ResMut<DeathTimer>,
+    time: Res<Time>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if death_timer.0.tick(time.delta()).just_finished() {
+        app_state.set(AppState::Dead.into());
+    }
 }
 
 fn exit_playing(mut commands: Commands, mut query: Query<(Entity, With<OrthographicProjection>)>) {
@@ -561,19 +814,47 @@ fn exit_deathscreen(mut commands: Commands, mut query: Query<Entity>) {
 }
 
 fn spawn_deathscreen_ui(
-    asset_server: Res<AssetServer>,
+    game_assets: Res<GameAssets>,
     mut commands: Commands,
-    mut query: Query<(&Score, With<Player>)>,
+    query: Query<(&Score, &PlayerIndex), With<Player>>,
     window_size_diag: Res<WindowSizeDiagonalWeighted>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    button_materials: Res<ButtonMaterials>,
+    window_size: Res<WindowSize>,
+    theme: Res<Theme>,
+    mode: Res<GameMode>,
+    death_snapshot: Option<Res<DeathSnapshot>>,
 ) {
     commands.spawn_bundle(UiCameraBundle::default());
 
-    let mut score = 0;
-    if let Ok((s, _)) = query.single_mut() {
-        score = s.0;
+    // the score of every player, in a race the first player is on the left
+    let mut scores = vec![0; mode.lanes() * mode.players_per_lane()];
+    for (score, player_index) in query.iter() {
+        if let Some(player_score) = scores.get_mut(player_index.0) {
+            *player_score = score.0;
+        }
     }
+    let (title, score_text) = match *mode {
+        GameMode::Single => ("Crashed!", format!("Score {}", scores[0])),
+        GameMode::SplitScreen => {
+            let title = match scores[0].cmp(&scores[1]) {
+                Ordering::Greater => "Left wins!",
+                Ordering::Less => "Right wins!",
+                Ordering::Equal => "Draw!",
+            };
+            (title, format!("Left {} - {} Right", scores[0], scores[1]))
+        }
+        GameMode::Versus => ("Crashed!", format!("Score {}", scores[0])),
+        GameMode::Coop => (
+            "Crashed!",
+            format!(
+                "Team {} (P1 {}, P2 {})",
+                scores[0] + scores[1],
+                scores[0],
+                scores[1]
+            ),
+        ),
+    };
+    // only single runs have a snapshot
+    let death_snapshot = death_snapshot.filter(|_| *mode == GameMode::Single);
 
     // UI
     commands
@@ -587,28 +868,100 @@ fn spawn_deathscreen_ui(
                 align_content: AlignContent::Center,
                 ..Default::default()
             },
-            material: materials.add(Color::rgb_u8(0x37, 0x41, 0x51).into()),
+            material: game_assets.deathscreen_material.clone(),
             ..Default::default()
         })
         .with_children(|parent| {
             // Score <x>
-            parent.spawn_bundle(TextBundle {
-                text: Text {
-                    alignment: TextAlignment {
-                        horizontal: HorizontalAlign::Center,
-                        vertical: VerticalAlign::Center,
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        alignment: TextAlignment {
+                            horizontal: HorizontalAlign::Center,
+                            vertical: VerticalAlign::Center,
+                        },
+                        sections: vec![TextSection {
+                            value: score_text,
+                            style: TextStyle {
+                                font_size: window_size_diag.0 * 0.07,
+                                font: game_assets.font_medium.clone(),
+                                color: theme.text_secondary,
+                            },
+                        }],
                     },
-                    sections: vec![TextSection {
-                        value: format!("Score {}", score),
-                        style: TextStyle {
-                            font_size: window_size_diag.0 * 0.07,
-                            font: asset_server.load("fonts/BaiJamjuree-Medium.ttf"),
-                            color: Color::rgb_u8(0xD1, 0xD5, 0xDB),
+                    ..Default::default()
+                })
+                .insert(UiFontSize(0.07))
+                .insert(ThemeText::Secondary);
+            // the rank of the run and the best online scores
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: game_assets.font_medium.clone(),
+                            font_size: window_size_diag.0 * 0.035,
+                            color: theme.text_secondary,
                         },
-                    }],
-                },
-                ..Default::default()
-            });
+                        TextAlignment {
+                            horizontal: HorizontalAlign::Center,
+                            vertical: VerticalAlign::Center,
+                        },
+                    ),
+                    ..Default::default()
+                })
+                .insert(UiFontSize(0.035))
+                .insert(ThemeText::Secondary)
+                .insert(LeaderboardText);
+            if *mode == GameMode::Versus {
+                // filled in once the opponent is done too
+                parent
+                    .spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            "",
+                            TextStyle {
+                                font: game_assets.font_medium.clone(),
+                                font_size: window_size_diag.0 * 0.05,
+                                color: theme.text,
+                            },
+                            TextAlignment::default(),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(UiFontSize(0.05))
+                    .insert(ThemeText::Primary)
+                    .insert(VersusResultText);
+            }
+            // Menu button
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        padding: Rect::all(Val::Px(8.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    material: game_assets.button_normal_material.clone(),
+                    ..Default::default()
+                })
+                .insert(ButtonAction::Menu)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "Menu",
+                                TextStyle {
+                                    font: game_assets.font_medium.clone(),
+                                    font_size: window_size_diag.0 * 0.05,
+                                    color: theme.text_secondary,
+                                },
+                                TextAlignment::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(UiFontSize(0.05))
+                        .insert(ThemeText::Secondary);
+                });
             // Respawn button
             parent
                 .spawn_bundle(ButtonBundle {
@@ -619,42 +972,74 @@ fn spawn_deathscreen_ui(
                         align_items: AlignItems::Center,
                         ..Default::default()
                     },
-                    material: button_materials.normal.clone(),
+                    material: game_assets.button_normal_material.clone(),
                     ..Default::default()
                 })
+                .insert(ButtonAction::Respawn)
                 .with_children(|parent| {
-                    parent.spawn_bundle(TextBundle {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "Respawn",
+                                TextStyle {
+                                    font: game_assets.font_medium.clone(),
+                                    font_size: window_size_diag.0 * 0.07,
+                                    color: theme.text_secondary,
+                                },
+                                TextAlignment::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(UiFontSize(0.07))
+                        .insert(ThemeText::Secondary);
+                });
+            if let Some(death_snapshot) = death_snapshot {
+                // the final moment
+                snapshot::spawn_snapshot(
+                    parent,
+                    &death_snapshot,
+                    &game_assets,
+                    0.3 * window_size.height,
+                );
+                // Hit a wall / Fell behind
+                parent
+                    .spawn_bundle(TextBundle {
                         text: Text::with_section(
-                            "Respawn",
+                            death_snapshot.cause.description(),
                             TextStyle {
-                                font: asset_server.load("fonts/BaiJamjuree-Medium.ttf"),
-                                font_size: window_size_diag.0 * 0.07,
-                                color: Color::rgb_u8(0xD1, 0xD5, 0xDB),
+                                font: game_assets.font_medium.clone(),
+                                font_size: window_size_diag.0 * 0.05,
+                                color: theme.text_secondary,
                             },
                             TextAlignment::default(),
                         ),
                         ..Default::default()
-                    });
-                });
+                    })
+                    .insert(UiFontSize(0.05))
+                    .insert(ThemeText::Secondary);
+            }
             // title
-            parent.spawn_bundle(TextBundle {
-                text: Text {
-                    sections: vec![TextSection {
-                        style: TextStyle {
-                            font_size: window_size_diag.0 * 0.12,
-                            font: asset_server.load("fonts/BaiJamjuree-Bold.ttf"),
-                            color: Color::rgb(1.0, 1.0, 1.0),
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            style: TextStyle {
+                                font_size: window_size_diag.0 * 0.12,
+                                font: game_assets.font_bold.clone(),
+                                color: theme.text,
+                            },
+                            value: title.into(),
+                        }],
+                        alignment: TextAlignment {
+                            horizontal: HorizontalAlign::Center,
+                            vertical: VerticalAlign::Center,
                         },
-                        value: "Crashed!".into(),
-                    }],
-                    alignment: TextAlignment {
-                        horizontal: HorizontalAlign::Center,
-                        vertical: VerticalAlign::Center,
+                        ..Default::default()
                     },
                     ..Default::default()
-                },
-                ..Default::default()
-            });
+                })
+                .insert(UiFontSize(0.12))
+            .insert(ThemeText::Primary);
         });
 }
 
@@ -664,13 +1049,16 @@ fn despawn_deathscreen_ui(mut commands: Commands, mut query: Query<Entity>) {
     }
 }
 
-fn apply_velocity(mut query: Query<(&mut Transform, &Velocity)>, time: Res<Time>) {
+fn apply_velocity(
+    mut query: Query<(&mut Transform, &Velocity), Without<FixedStep>>,
+    time: Res<Time>,
+) {
     for (mut tf, vel) in query.iter_mut() {
         tf.translation += vel.0 * time.delta_seconds();
     }
 }
 
-fn friction(mut query: Query<&mut Velocity>, time: Res<Time>) {
+fn friction(mut query: Query<&mut Velocity, Without<FixedStep>>, time: Res<Time>) {
     for mut vel in query.iter_mut() {
         let vel_length = vel.0.length();
         if vel_length < VEL_CLIPPING {
@@ -685,73 +1073,78 @@ fn friction(mut query: Query<&mut Velocity>, time: Res<Time>) {
 }
 
 fn gesture_on_player(
-    mouse_buttons: Res<Input<MouseButton>>,
-    windows: Res<Windows>,
-    mut drag_gesture: ResMut<DragGesture>,
+    input: PlayerInput,
     mut query_set: QuerySet<(
-        Query<(&Transform, &Sprite, &mut Velocity, With<Player>)>,
-        Query<(&Transform, With<OrthographicProjection>)>,
-        Query<(Entity, &mut Transform, &mut Sprite), With<GestureLine>>,
+        Query<
+            (
+                Entity,
+                &Transform,
+                &mut Velocity,
+                &mut DragGesture,
+                &mut Stamina,
+                &Controller,
+                &Lane,
+                Option<&mut bot::Autopilot>,
+            ),
+            (With<Player>, Without<Crashed>),
+        >,
+        Query<(&Transform, &Lane), With<OrthographicProjection>>,
+        Query<(Entity, &mut Transform, &mut Sprite, &GestureLine)>,
     )>,
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+    game_assets: Res<GameAssets>,
+    mut player_pushed_event: EventWriter<PlayerPushedEvent>,
 ) {
-    let window = windows.get_primary().unwrap();
-    let mut camera_y = 0.0f32;
-    if let Ok((tf, _)) = query_set.q1_mut().single_mut() {
-        camera_y = tf.translation.y;
-    }
-    let mut is_left_pressed = false;
-    let mut player_pos_real = Vec3::ZERO;
-    if let Ok((tf, _sprite, mut vel, _)) = query_set.q0_mut().single_mut() {
-        let mut player_pos = tf.translation.clone();
-        player_pos_real = tf.translation.clone();
-        player_pos.y -= camera_y;
-        is_left_pressed = mouse_buttons.pressed(MouseButton::Left);
-        if is_left_pressed {
-            if !drag_gesture.is_dragging {
-                if let Some(_pos) = window.cursor_position() {
-                    drag_gesture.start_pos = _pos;
-                    drag_gesture.is_dragging = true;
-                }
-
+    // converts distances on the screen into world units
+    let mut camera_scales = Vec::new();
+    for (tf, lane) in query_set.q1().iter() {
+        camera_scales.push((*lane, tf.scale.x));
+    }
+    // the players that are dragging, with their position and drag vector
+    let mut drags = Vec::new();
+    for (player, tf, mut vel, mut drag_gesture, mut stamina, controller, lane, autopilot) in
+        query_set.q0_mut().iter_mut()
+    {
+        let camera_scale = camera_scales
+            .iter()
+            .find(|(camera_lane, _)| camera_lane == lane)
+            .map_or(1.0, |(_, scale)| *scale);
+        let was_dragging = drag_gesture.is_dragging;
+        let released = match autopilot {
+            Some(mut autopilot) => autopilot.update(&mut drag_gesture),
+            None => input.update(*controller, &mut drag_gesture, camera_scale),
+        };
+        if let Some(mut drag_vector) = released {
+            // weaken the push if there is not enough stamina left
+            drag_vector *= stamina.spend(drag_vector.length());
+            let impulse = -drag_vector * PLAYER_SPEED;
+            vel.0.x += impulse.x;
+            vel.0.y += impulse.y;
+            player_pushed_event.send(PlayerPushedEvent { player, impulse });
+        }
+        if drag_gesture.is_dragging {
+            if !was_dragging {
                 // spawn line
                 commands
                     .spawn_bundle(SpriteBundle {
                         sprite: Sprite::new(Vec2::new(3.0, 0.0)),
-                        material: materials
-                            .add(Color::rgba_u8(0xD1, 0xD5, 0xDB, 0x80).into())
-                            .clone(),
-                        transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                        material: game_assets.gesture_line_material.clone(),
+                        transform: Transform::from_xyz(tf.translation.x, tf.translation.y, 1.0),
                         ..Default::default()
                     })
-                    .insert(GestureLine);
-            }
-        }
-        if !mouse_buttons.pressed(MouseButton::Left) {
-            if drag_gesture.is_dragging {
-                drag_gesture.is_dragging = false;
-                if let Some(_pos) = window.cursor_position() {
-                    let drag_vector = _pos - drag_gesture.start_pos;
-                    vel.0.x -= drag_vector.x * PLAYER_SPEED;
-                    vel.0.y -= drag_vector.y * PLAYER_SPEED;
-                }
-
-                // despawn line
-                for (entity, _, __) in query_set.q2_mut().iter_mut() {
-                    commands.entity(entity).despawn();
-                }
+                    .insert(GestureLine(player))
+                    .insert(*lane);
             }
+            drags.push((player, tf.translation.truncate(), drag_gesture.vector));
         }
     }
 
-    // draw line
-    if is_left_pressed {
-        for (_, mut tf, mut sprite) in query_set.q2_mut().iter_mut() {
-            if let Some(mouse_pos) = window.cursor_position() {
-                let diff = mouse_pos - drag_gesture.start_pos;
+    // draw lines, the lines of released gestures and crashed players are despawned
+    for (entity, mut tf, mut sprite, line) in query_set.q2_mut().iter_mut() {
+        match drags.iter().find(|(player, _, _)| *player == line.0) {
+            Some((_, player_pos, diff)) => {
                 let length = diff.length();
-                let center = Vec2::new(player_pos_real.x, player_pos_real.y) + 0.5 * diff;
+                let center = *player_pos + 0.5 * *diff;
                 let rotation = diff.angle_between(Vec2::Y);
 
                 tf.translation.x = center.x;
@@ -759,69 +1152,110 @@ fn gesture_on_player(
                 tf.rotation = Quat::from_rotation_z(-rotation);
                 sprite.size.y = length;
             }
+            None => {
+                commands.entity(entity).despawn();
+            }
         }
     }
 }
 
 fn button_system(
-    button_materials: Res<ButtonMaterials>,
+    game_assets: Res<GameAssets>,
     mut interaction_query: Query<
-        (&Interaction, &mut Handle<ColorMaterial>, &Children),
+        (&Interaction, &mut Handle<ColorMaterial>, Option<&ButtonAction>),
         (Changed<Interaction>, With<Button>),
     >,
     mut app_state: ResMut<State<AppState>>,
+    mut mode: ResMut<GameMode>,
 ) {
-    for (interaction, mut material, _children) in interaction_query.iter_mut() {
+    for (interaction, mut material, action) in interaction_query.iter_mut() {
         match *interaction {
             Interaction::Clicked => {
-                *material = button_materials.pressed.clone();
+                *material = game_assets.button_pressed_material.clone();
                 if app_state.current() == &AppState::Paused {
                     app_state.pop();
                 }
-                else if app_state.current() == &AppState::Dead { 
-                    app_state.set(AppState::Playing.into());
+                else {
+                    match action {
+                        Some(ButtonAction::Play) => {
+                            *mode = GameMode::Single;
+                            app_state.set(AppState::Playing.into());
+                        }
+                        Some(ButtonAction::Race) => {
+                            *mode = GameMode::SplitScreen;
+                            app_state.set(AppState::Playing.into());
+                        }
+                        Some(ButtonAction::Coop) => {
+                            *mode = GameMode::Coop;
+                            app_state.set(AppState::Playing.into());
+                        }
+                        Some(ButtonAction::Versus) => {
+                            app_state.set(AppState::Connecting.into());
+                        }
+                        // a versus match needs the other side again
+                        Some(ButtonAction::Respawn) if *mode == GameMode::Versus => {
+                            app_state.set(AppState::Connecting.into());
+                        }
+                        Some(ButtonAction::Respawn) => {
+                            app_state.set(AppState::Playing.into());
+                        }
+                        Some(ButtonAction::Menu) => {
+                            app_state.set(AppState::Menu.into());
+                        }
+                        Some(ButtonAction::Achievements) => {
+                            app_state.set(AppState::Achievements.into());
+                        }
+                        Some(ButtonAction::Stats) => {
+                            app_state.set(AppState::Stats.into());
+                        }
+                        _ => {}
+                    }
                 }
             }
             Interaction::Hovered => {
-                *material = button_materials.hovered.clone();
+                *material = game_assets.button_hovered_material.clone();
             }
             Interaction::None => {
-                *material = button_materials.normal.clone();
+                *material = game_assets.button_normal_material.clone();
             }
         }
     }
 }
 
-fn score_change(
-    audio: Res<Audio>,
-    score_sound: Res<ScoreSound>,
-    mut query: Query<&Score, (With<Player>, Changed<Score>)>,
-) {
-    if let Ok(score) = query.single_mut() {
-        if score.0 > 0 {
-            // audio.play(asset_server.load("sounds/pickupCoin.wav").into());
-            audio.play(score_sound.0.clone())
-        }
-    }
-}
-
+// the play area is in world units and only needs the camera to be fitted again (see camera::fit_view),
+// but the ui is laid out in pixels
 fn handle_resize(
     mut resize_event: EventReader<WindowResized>,
     mut window_size_diag: ResMut<WindowSizeDiagonalWeighted>,
     mut window_size: ResMut<WindowSize>,
+    mut ui_text_query: Query<(&mut Text, &UiFontSize)>,
 ) {
+    let mut resized = false;
     for ev in resize_event.iter() {
         // println!("Resize: w={}, h={}", ev.width, ev.height);
         window_size.width = ev.width;
         window_size.height = ev.height;
         window_size_diag.0 = ((ev.width * ev.width + ev.height * ev.height) / 2.0).sqrt();
+        resized = true;
+    }
+    if resized {
+        for (mut text, font_size) in ui_text_query.iter_mut() {
+            for section in text.sections.iter_mut() {
+                section.style.font_size = window_size_diag.0 * font_size.0;
+            }
+        }
     }
 }
 
 fn window_focus(
     mut window_focused: EventReader<WindowFocused>,
-    mut app_state: ResMut<State<AppState>>
+    mut app_state: ResMut<State<AppState>>,
+    mode: Res<GameMode>,
 ) {
+    // the opponent doesn't wait
+    if *mode == GameMode::Versus {
+        return;
+    }
     for ev in window_focused.iter() {
         if !ev.focused && app_state.current().clone() == AppState::Playing {
             app_state.push(AppState::Paused.into());
@@ -831,8 +1265,12 @@ fn window_focus(
 
 fn esc_pause_check(
     input_buttons: Res<Input<KeyCode>>,
-    mut app_state: ResMut<State<AppState>>
+    mut app_state: ResMut<State<AppState>>,
+    mode: Res<GameMode>,
 ) {
+    if *mode == GameMode::Versus {
+        return;
+    }
     if input_buttons.just_pressed(KeyCode::Escape) {
         app_state.push(AppState::Paused.into());
     }
@@ -841,37 +1279,38 @@ fn esc_pause_check(
 
 fn spawn_pause_ui(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut query: Query<&Transform, With<OrthographicProjection>>,
-    window_size_diag: Res<WindowSizeDiagonalWeighted>
+    game_assets: Res<GameAssets>,
+    lane_views: Res<LaneViews>,
+    theme: Res<Theme>,
 ) {
     // println!("Spawn pause ui");
 
-    let mut camera_y: f32 = 0.0;
-    if let Ok(tf) = query.single_mut() {
-        camera_y = tf.translation.y;
-    }
-    commands.spawn_bundle(Text2dBundle {
-        text: Text {
-            alignment: TextAlignment {
-                horizontal: HorizontalAlign::Center,
-                vertical: VerticalAlign::Center
-            },
-            sections: vec![
-                TextSection {
-                    value: "Continue".to_string(),
-                    style: TextStyle {
-                        color: Color::WHITE,
-                        font: asset_server.load("fonts/BaiJamjuree-Medium.ttf"),
-                        font_size: window_size_diag.0 * 0.07
+    // in the middle of every lane
+    for (index, view) in lane_views.0.iter().enumerate() {
+        let lane = Lane(index);
+        commands.spawn_bundle(Text2dBundle {
+            text: Text {
+                alignment: TextAlignment {
+                    horizontal: HorizontalAlign::Center,
+                    vertical: VerticalAlign::Center
+                },
+                sections: vec![
+                    TextSection {
+                        value: "Continue".to_string(),
+                        style: TextStyle {
+                            color: theme.text,
+                            font: game_assets.font_medium.clone(),
+                            font_size: PLAY_DIAGONAL_WEIGHTED * 0.07
+                        }
                     }
-                }
-            ]
-        },
-        transform: Transform::from_xyz(0.0, camera_y, 99.0),
-        ..Default::default()
-    })
-        .insert(PauseMenuText);
+                ]
+            },
+            transform: Transform::from_xyz(lane.x(), view.camera_y, 99.0),
+            ..Default::default()
+        })
+            .insert(PauseMenuText)
+            .insert(ThemeText::Primary);
+    }
 }
 
 fn unpause_check(
@@ -894,9 +1333,13 @@ fn despawn_pause_ui(
 }
 
 fn main() {
+    let mut settings = Settings::load();
+    let autopilot_trial = bot::AutopilotTrial::from_args();
+    settings.autopilot_override = autopilot_trial.skill;
+    let theme = theme::load_theme(&settings);
     App::build()
         // .insert_resource(Msaa { samples: 2 })
-        .insert_resource(ClearColor(Color::rgb_u8(52, 103, 235)))
+        .insert_resource(ClearColor(theme.background))
         .insert_resource(WindowDescriptor {
             title: "Endless game".to_string(),
             width: 720.0,
@@ -906,44 +1349,252 @@ fn main() {
             vsync: true,
             ..Default::default()
         })
+        .insert_resource(settings)
+        .insert_resource(Progress::load())
+        .insert_resource(Ghosts::load())
+        .insert_resource(Leaderboard::default())
+        .insert_resource(Network::from_args())
+        .insert_resource(autopilot_trial)
+        .insert_resource(theme)
+        .insert_resource(SfxChannels::default())
+        .insert_resource(GameMode::default())
         .add_plugins(DefaultPlugins)
-        // .add_plugin(AudioPlugin)
+        .add_plugin(AudioPlugin)
         .add_event::<FoodEatenEvent>()
         .add_event::<GatePassedEvent>()
+        .add_event::<CrashReport>()
         .add_event::<PlayerLostEvent>()
-        .add_state(AppState::Playing)
+        .add_event::<PlayerPushedEvent>()
+        .add_state(AppState::Loading)
+        .add_system(theme::switch_theme.system())
+        .add_system(colorblind::switch_colorblind_mode.system())
+        .add_system(theme::apply_theme.system())
+        .add_system(music::update_music.system())
+        .add_system(audio::toggle_mute.system())
+        .add_system(audio::apply_sfx_volume.system())
+        .add_system(sounds::click_sounds.system())
+        .add_system_set(
+            SystemSet::on_enter(AppState::Loading)
+                .with_system(game_assets::start_loading.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Loading)
+                .with_system(game_assets::check_loading.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Loading)
+                .with_system(game_assets::exit_loading.system())
+                .with_system(music::start_music.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Menu)
+                .with_system(skins::check_selected_skin.system().label("check_skin"))
+                .with_system(menu::spawn_menu.system().after("check_skin")),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Menu)
+                .with_system(button_system.system())
+                .with_system(menu::skin_buttons.system())
+                .with_system(menu::update_skin_preview.system())
+                .with_system(ghost::load_dropped_ghost.system())
+                .with_system(bot::run_trial.system())
+                .with_system(handle_resize.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Menu).with_system(menu::exit_menu.system()),
+        )
         .add_system_set(
-            SystemSet::on_enter(AppState::Playing).with_system(setup.system()),
+            SystemSet::on_enter(AppState::Achievements)
+                .with_system(achievements::spawn_achievements_screen.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Achievements)
+                .with_system(button_system.system())
+                .with_system(handle_resize.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Achievements)
+                .with_system(achievements::exit_achievements_screen.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Stats)
+                .with_system(stats::spawn_stats_screen.system())
+                .with_system(leaderboard::fetch_top.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Stats)
+                .with_system(button_system.system())
+                .with_system(leaderboard::update_leaderboard_text.system())
+                .with_system(handle_resize.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Stats).with_system(menu::exit_screen.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Connecting)
+                .with_system(versus::spawn_connecting_screen.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Connecting)
+                .with_system(versus::connect.system())
+                .with_system(button_system.system())
+                .with_system(handle_resize.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Connecting).with_system(menu::exit_screen.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Playing)
+                .with_system(setup.system())
+                .with_system(lanes::add_right_pass.system())
+                .with_system(versus::spawn_opponent.system()),
         )
         .add_system_set(
             SystemSet::on_update(AppState::Playing)
                 .with_system(apply_velocity.system())
-                .with_system(friction.system())
-                .with_system(camera_movement.system().label("camera_movement"))
+                .with_system(friction.system().label("friction"))
+                .with_system(camera::camera_movement.system().label("camera_movement"))
+                .with_system(camera::camera_shake.system().label("camera_shake"))
+                .with_system(
+                    camera::fit_view
+                        .system()
+                        .after("camera_movement")
+                        .after("camera_shake"),
+                )
+                .with_system(
+                    camera::update_letterbox
+                        .system()
+                        .after("camera_movement")
+                        .after("camera_shake"),
+                )
                 .with_system(player_check_food.system().label("check_food"))
-                .with_system(food_eaten.system().after("check_food"))
-                .with_system(player_check_leave_view.system())
+                .with_system(food_eaten.system().label("food_eaten").after("check_food"))
+                .with_system(player_check_leave_view.system().label("leave_view"))
                 .with_system(handle_entities_out_of_range.system())
                 .with_system(spawn_walls.system())
-                .with_system(player_wall_collision.system())
+                .with_system(player_wall_collision.system().label("wall_collision"))
                 .with_system(player_gate_collision.system().label("gate_collision"))
-                .with_system(gate_passed.system().after("gate_collision"))
-                .with_system(gesture_on_player.system())
+                .with_system(gate_passed.system().label("gate_passed").after("gate_collision"))
+                .with_system(
+                    particles::emit_particles
+                        .system()
+                        .label("emit_particles")
+                        .after("check_food")
+                        .after("gate_collision")
+                        .after("player_lost"),
+                )
+                .with_system(particles::update_particles.system().after("emit_particles"))
+                .with_system(
+                    snapshot::take_snapshot
+                        .system()
+                        .after("check_food")
+                        .after("gate_collision")
+                        .after("player_lost"),
+                )
+                .with_system(trail::update_trail.system())
+                .with_system(
+                    stats::track_run
+                        .system()
+                        .label("track_run")
+                        .after("check_food")
+                        .after("gate_collision")
+                        .after("player_lost"),
+                )
+                .with_system(achievements::check_achievements.system().after("track_run"))
+                .with_system(achievements::update_toasts.system().after("camera_movement"))
+                .with_system(background::update_starfield.system().after("camera_movement"))
+                .with_system(art::attach_art.system())
+                .with_system(
+                    art::squash_player
+                        .system()
+                        .after("friction")
+                        .after("versus_step"),
+                )
+                .with_system(art::follow_targets.system())
+                .with_system(art::animate_sprites.system())
+                .with_system(bot::drive_bots.system().before("gesture"))
+                .with_system(gesture_on_player.system().label("gesture"))
                 .with_system(update_scoreboard.system().after("camera_movement"))
-                .with_system(score_change.system())
+                .with_system(stamina::regenerate_stamina.system())
+                .with_system(stamina::update_stamina_bar.system().after("camera_movement"))
+                .with_system(
+                    sounds::play_event_sounds
+                        .system()
+                        .after("check_food")
+                        .after("gate_collision")
+                        .after("player_lost"),
+                )
+                .with_system(sounds::check_near_misses.system())
+                .with_system(coop::revive_players.system())
+                .with_system(versus::advance_clock.system().label("versus_clock"))
+                .with_system(
+                    versus::step_local_player
+                        .system()
+                        .label("versus_step")
+                        .after("versus_clock")
+                        .after("gesture")
+                        .after("camera_movement")
+                        .before("leave_view")
+                        .before("wall_collision"),
+                )
+                .with_system(
+                    versus::sync
+                        .system()
+                        .after("versus_step")
+                        .after("gate_collision"),
+                )
+                .with_system(versus::update_opponent.system().after("camera_movement"))
+                .with_system(
+                    // the replay needs the score and the gates of the same frame
+                    ghost::record_ghost
+                        .system()
+                        .label("record_ghost")
+                        .after("track_run")
+                        .after("food_eaten")
+                        .after("gate_passed"),
+                )
+                .with_system(ghost::replay_ghost.system().after("record_ghost"))
+                .with_system(ghost::update_ghost_delta.system().after("record_ghost"))
                 .with_system(handle_resize.system())
                 .with_system(window_focus.system())
                 .with_system(esc_pause_check.system())
-                .with_system(player_lost.system()),
+                .with_system(
+                    player_lost
+                        .system()
+                        .label("player_lost")
+                        .after("wall_collision")
+                        .after("leave_view"),
+                ),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Dying)
+                .with_system(death_delay.system())
+                .with_system(versus::advance_clock.system().label("versus_clock"))
+                .with_system(versus::sync.system().after("versus_clock"))
+                .with_system(particles::update_particles.system())
+                .with_system(art::animate_sprites.system())
+                .with_system(camera::camera_shake.system().label("camera_shake"))
+                .with_system(camera::fit_view.system().after("camera_shake"))
+                .with_system(handle_resize.system()),
         )
-        .add_system_set(SystemSet::on_exit(AppState::Playing).with_system(exit_playing.system()))
+        .add_system_set(SystemSet::on_exit(AppState::Dying).with_system(exit_playing.system()))
         .add_system_set(
             SystemSet::on_enter(AppState::Dead)
-                .with_system(spawn_deathscreen_ui.system()),
+                .with_system(spawn_deathscreen_ui.system())
+                .with_system(stats::record_run.system())
+                .with_system(ghost::save_ghost.system())
+                .with_system(leaderboard::submit_run.system()),
         )
         .add_system_set(
             SystemSet::on_update(AppState::Dead)
-                .with_system(button_system.system()),
+                .with_system(button_system.system())
+                .with_system(ghost::load_dropped_ghost.system())
+                .with_system(leaderboard::update_leaderboard_text.system())
+                .with_system(versus::advance_clock.system().label("versus_clock"))
+                .with_system(versus::sync.system().after("versus_clock"))
+                .with_system(versus::update_result_text.system())
+                .with_system(bot::run_trial.system())
+                .with_system(handle_resize.system()),
         )
         .add_system_set(
             SystemSet::on_exit(AppState::Dead)
@@ -956,7 +1607,9 @@ fn main() {
         )
         .add_system_set(
             SystemSet::on_update(AppState::Paused)
-                .with_system(unpause_check.system()),
+                .with_system(unpause_check.system())
+                .with_system(camera::fit_view.system())
+                .with_system(handle_resize.system()),
         )
         .add_system_set(
             SystemSet::on_exit(AppState::Paused)
@@ -0,0 +1,433 @@
+use crate::{
+    game_assets::GameAssets,
+    progress::Progress,
+    settings::Settings,
+    skins::{self, SKINS},
+    theme::{self, Theme, ThemeText},
+    versus::Network,
+    ButtonAction, UiFontSize, WindowSize, WindowSizeDiagonalWeighted,
+};
+use bevy::prelude::*;
+
+// Resource: the skin that is shown in the menu, it is only selected if it is unlocked
+pub struct MenuSkin(usize);
+
+// components
+pub struct SkinNameText;
+pub struct SkinHintText;
+pub struct SkinPreview;
+pub struct SkinLockedText;
+
+// opacity of a locked skin in the preview
+const LOCKED_PREVIEW_ALPHA: f32 = 0.3;
+
+fn text_bundle(
+    value: &str,
+    font: Handle<Font>,
+    font_size: f32,
+    window_size_diag: f32,
+    color: Color,
+) -> TextBundle {
+    TextBundle {
+        text: Text::with_section(
+            value,
+            TextStyle {
+                font,
+                font_size: window_size_diag * font_size,
+                color,
+            },
+            TextAlignment {
+                horizontal: HorizontalAlign::Center,
+                vertical: VerticalAlign::Center,
+            },
+        ),
+        ..Default::default()
+    }
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    game_assets: &GameAssets,
+    theme: &Theme,
+    window_size_diag: f32,
+    label: &str,
+    action: ButtonAction,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                padding: Rect::all(Val::Px(8.0)),
+                margin: Rect::all(Val::Px(8.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: game_assets.button_normal_material.clone(),
+            ..Default::default()
+        })
+        .insert(action)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(text_bundle(
+                    label,
+                    game_assets.font_medium.clone(),
+                    0.07,
+                    window_size_diag,
+                    theme.text_secondary,
+                ))
+                .insert(UiFontSize(0.07))
+                .insert(ThemeText::Secondary);
+        });
+}
+
+pub fn spawn_menu(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    game_assets: Res<GameAssets>,
+    theme: Res<Theme>,
+    settings: Res<Settings>,
+    progress: Res<Progress>,
+    network: Res<Network>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    // the menu comes before the game, so the window size is not known yet
+    let window = windows.get_primary().unwrap();
+    let (width, height) = (window.width(), window.height());
+    let window_size_diag = ((width * width + height * height) / 2.0).sqrt();
+    commands.insert_resource(WindowSize { width, height });
+    commands.insert_resource(WindowSizeDiagonalWeighted(window_size_diag));
+
+    let skin_index = skins::skin_index(&settings.skin);
+    commands.insert_resource(MenuSkin(skin_index));
+
+    commands.spawn_bundle(UiCameraBundle::default());
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::SpaceAround,
+                ..Default::default()
+            },
+            material: game_assets.deathscreen_material.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(text_bundle(
+                    "Endless game",
+                    game_assets.font_bold.clone(),
+                    0.12,
+                    window_size_diag,
+                    theme.text,
+                ))
+                .insert(UiFontSize(0.12))
+                .insert(ThemeText::Primary);
+            parent
+                .spawn_bundle(text_bundle(
+                    &format!("Best score {}", progress.best_score),
+                    game_assets.font_medium.clone(),
+                    0.05,
+                    window_size_diag,
+                    theme.text_secondary,
+                ))
+                .insert(UiFontSize(0.05))
+                .insert(ThemeText::Secondary);
+
+            // skin selection: < preview >
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    material: game_assets.ui_transparent_material.clone(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    spawn_button(
+                        parent,
+                        &game_assets,
+                        &theme,
+                        window_size_diag,
+                        "<",
+                        ButtonAction::PreviousSkin,
+                    );
+                    // shows the color and shape of the browsed skin, see update_skin_preview
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(48.0), Val::Px(48.0)),
+                                margin: Rect::all(Val::Px(16.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..Default::default()
+                            },
+                            material: materials.add(ColorMaterial::modulated_texture(
+                                game_assets.shape_textures.plain.clone(),
+                                theme.player,
+                            )),
+                            ..Default::default()
+                        })
+                        .insert(SkinPreview)
+                        .with_children(|parent| {
+                            parent
+                                .spawn_bundle(text_bundle(
+                                    "",
+                                    game_assets.font_bold.clone(),
+                                    0.03,
+                                    window_size_diag,
+                                    theme.text,
+                                ))
+                                .insert(UiFontSize(0.03))
+                                .insert(ThemeText::Primary)
+                                .insert(SkinLockedText);
+                        });
+                    spawn_button(
+                        parent,
+                        &game_assets,
+                        &theme,
+                        window_size_diag,
+                        ">",
+                        ButtonAction::NextSkin,
+                    );
+                });
+            parent
+                .spawn_bundle(text_bundle(
+                    SKINS[skin_index].name,
+                    game_assets.font_medium.clone(),
+                    0.06,
+                    window_size_diag,
+                    theme.text,
+                ))
+                .insert(UiFontSize(0.06))
+                .insert(ThemeText::Primary)
+                .insert(SkinNameText);
+            parent
+                .spawn_bundle(text_bundle(
+                    "",
+                    game_assets.font_medium.clone(),
+                    0.04,
+                    window_size_diag,
+                    theme.text_secondary,
+                ))
+                .insert(UiFontSize(0.04))
+                .insert(ThemeText::Secondary)
+                .insert(SkinHintText);
+
+            spawn_button(
+                parent,
+                &game_assets,
+                &theme,
+                window_size_diag,
+                "Play",
+                ButtonAction::Play,
+            );
+            // split screen for two players
+            spawn_button(
+                parent,
+                &game_assets,
+                &theme,
+                window_size_diag,
+                "Race",
+                ButtonAction::Race,
+            );
+            // two players in one lane
+            spawn_button(
+                parent,
+                &game_assets,
+                &theme,
+                window_size_diag,
+                "Co-op",
+                ButtonAction::Coop,
+            );
+            // only with --versus on the command line
+            if network.enabled() {
+                spawn_button(
+                    parent,
+                    &game_assets,
+                    &theme,
+                    window_size_diag,
+                    "Versus",
+                    ButtonAction::Versus,
+                );
+            }
+            spawn_button(
+                parent,
+                &game_assets,
+                &theme,
+                window_size_diag,
+                "Achievements",
+                ButtonAction::Achievements,
+            );
+            spawn_button(
+                parent,
+                &game_assets,
+                &theme,
+                window_size_diag,
+                "Stats",
+                ButtonAction::Stats,
+            );
+        });
+}
+
+// shows the next or previous skin and selects it if it is unlocked
+pub fn skin_buttons(
+    interaction_query: Query<(&Interaction, &ButtonAction), Changed<Interaction>>,
+    mut menu_skin: ResMut<MenuSkin>,
+    mut settings: ResMut<Settings>,
+    mut theme: ResMut<Theme>,
+    progress: Res<Progress>,
+    mut query_set: QuerySet<(
+        Query<&mut Text, With<SkinNameText>>,
+        Query<&mut Text, With<SkinHintText>>,
+    )>,
+) {
+    for (interaction, action) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        menu_skin.0 = match action {
+            ButtonAction::PreviousSkin => (menu_skin.0 + SKINS.len() - 1) % SKINS.len(),
+            ButtonAction::NextSkin => (menu_skin.0 + 1) % SKINS.len(),
+            _ => continue,
+        };
+        let skin = &SKINS[menu_skin.0];
+        let unlocked = skin.is_unlocked(&progress);
+        if unlocked {
+            settings.skin = skin.name.to_string();
+            settings.save();
+            *theme = theme::load_theme(&settings);
+        }
+        if let Ok(mut text) = query_set.q0_mut().single_mut() {
+            text.sections[0].value = skin.name.to_string();
+        }
+        if let Ok(mut text) = query_set.q1_mut().single_mut() {
+            text.sections[0].value = if unlocked {
+                String::new()
+            } else {
+                skin.unlock_hint()
+            };
+        }
+    }
+}
+
+// the preview shows the browsed skin even if it is locked, a locked skin is faded out and labeled
+pub fn update_skin_preview(
+    menu_skin: Res<MenuSkin>,
+    theme: Res<Theme>,
+    settings: Res<Settings>,
+    progress: Res<Progress>,
+    game_assets: Res<GameAssets>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    preview_query: Query<&Handle<ColorMaterial>, With<SkinPreview>>,
+    added_query: Query<Entity, Added<SkinPreview>>,
+    mut text_query: Query<&mut Text, With<SkinLockedText>>,
+) {
+    if !menu_skin.is_changed() && !theme.is_changed() && added_query.iter().next().is_none() {
+        return;
+    }
+    let skin = &SKINS[menu_skin.0];
+    // the theme the browsed skin would get, the current theme has the selected skin
+    let mut browsed_settings = settings.clone();
+    browsed_settings.skin = skin.name.to_string();
+    let browsed_theme = theme::load_theme(&browsed_settings);
+    let unlocked = skin.is_unlocked(&progress);
+    let mut color = browsed_theme.player;
+    if !unlocked {
+        color.set_a(color.a() * LOCKED_PREVIEW_ALPHA);
+    }
+    for handle in preview_query.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.color = color;
+            material.texture = Some(
+                game_assets
+                    .shape_textures
+                    .player(browsed_theme.player_shape)
+                    .clone(),
+            );
+        }
+    }
+    if let Ok(mut text) = text_query.single_mut() {
+        text.sections[0].value = if unlocked {
+            String::new()
+        } else {
+            "Locked".to_string()
+        };
+    }
+}
+
+// a title, one line of text per entry and a back button
+pub fn spawn_list_screen(
+    commands: &mut Commands,
+    game_assets: &GameAssets,
+    theme: &Theme,
+    window_size_diag: f32,
+    title: &str,
+    lines: Vec<(String, ThemeText)>,
+) -> Vec<Entity> {
+    commands.spawn_bundle(UiCameraBundle::default());
+    let mut line_entities = Vec::new();
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::SpaceAround,
+                ..Default::default()
+            },
+            material: game_assets.deathscreen_material.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(text_bundle(
+                    title,
+                    game_assets.font_bold.clone(),
+                    0.08,
+                    window_size_diag,
+                    theme.text,
+                ))
+                .insert(UiFontSize(0.08))
+                .insert(ThemeText::Primary);
+            for (line, theme_text) in lines {
+                let line_entity = parent
+                    .spawn_bundle(text_bundle(
+                        &line,
+                        game_assets.font_medium.clone(),
+                        0.035,
+                        window_size_diag,
+                        theme_text.color(theme),
+                    ))
+                    .insert(UiFontSize(0.035))
+                    .insert(theme_text)
+                    .id();
+                line_entities.push(line_entity);
+            }
+            spawn_button(
+                parent,
+                game_assets,
+                theme,
+                window_size_diag,
+                "Back",
+                ButtonAction::Menu,
+            );
+        });
+    line_entities
+}
+
+// despawns everything of a screen
+pub fn exit_screen(mut commands: Commands, query: Query<Entity>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn exit_menu(mut commands: Commands, query: Query<Entity>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<MenuSkin>();
+}
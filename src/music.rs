@@ -0,0 +1,105 @@
+use crate::{camera::CameraState, game_assets::GameAssets, settings::Settings, AppState};
+use bevy::prelude::*;
+use bevy_kira_audio::{Audio, AudioChannel};
+
+// volume change per second, a full crossfade takes 1 / FADE_SPEED seconds
+const FADE_SPEED: f32 = 0.8;
+// volume of the game music while the game is paused
+const PAUSED_VOLUME: f32 = 0.3;
+// camera speedup at which the intensity layer is at full volume
+const FULL_INTENSITY_SPEEDUP: f32 = 1.0;
+
+struct MusicLayer {
+    channel: AudioChannel,
+    // 0.0 - 1.0, before the volume settings are applied
+    fade: f32,
+    // what was last sent to the channel
+    volume: f32,
+}
+
+impl MusicLayer {
+    fn new(name: &str) -> Self {
+        MusicLayer {
+            channel: AudioChannel::new(name.to_string()),
+            fade: 0.0,
+            volume: 0.0,
+        }
+    }
+
+    // moves the fade towards the target and sends the volume to the channel if it changed
+    fn fade_to(&mut self, target: f32, delta: f32, music_volume: f32, audio: &Audio) {
+        self.fade = if self.fade < target {
+            (self.fade + FADE_SPEED * delta).min(target)
+        } else {
+            (self.fade - FADE_SPEED * delta).max(target)
+        };
+        let volume = self.fade * music_volume;
+        if volume != self.volume {
+            self.volume = volume;
+            audio.set_volume_in_channel(volume, &self.channel);
+        }
+    }
+}
+
+// Resource: every layer loops all the time, only the volumes change
+pub struct Music {
+    // the game music
+    base: MusicLayer,
+    // drums and arpeggio on top of the base, louder with the scroll speed
+    intensity: MusicLayer,
+    // menus and deathscreen
+    calm: MusicLayer,
+}
+
+// starts all layers at the same time so they stay in sync
+pub fn start_music(mut commands: Commands, audio: Res<Audio>, game_assets: Res<GameAssets>) {
+    let music = Music {
+        base: MusicLayer::new("music_base"),
+        intensity: MusicLayer::new("music_intensity"),
+        calm: MusicLayer::new("music_calm"),
+    };
+    for (layer, source) in [
+        (&music.base, &game_assets.music_base),
+        (&music.intensity, &game_assets.music_intensity),
+        (&music.calm, &game_assets.music_calm),
+    ]
+    .iter()
+    {
+        audio.set_volume_in_channel(0.0, &layer.channel);
+        audio.play_looped_in_channel((*source).clone(), &layer.channel);
+    }
+    commands.insert_resource(music);
+}
+
+// crossfades between the layers depending on the state and the scroll speed
+pub fn update_music(
+    audio: Res<Audio>,
+    music: Option<ResMut<Music>>,
+    app_state: Res<State<AppState>>,
+    camera_query: Query<&CameraState>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let mut music = match music {
+        Some(music) => music,
+        None => return,
+    };
+    // the fastest lane sets the intensity
+    let speedup = camera_query
+        .iter()
+        .map(|camera_state| camera_state.speedup)
+        .fold(0.0, f32::max);
+    let intensity = (speedup / FULL_INTENSITY_SPEEDUP).min(1.0);
+    let (base, intensity, calm) = match app_state.current() {
+        AppState::Playing => (1.0, intensity, 0.0),
+        AppState::Paused => (PAUSED_VOLUME, PAUSED_VOLUME * intensity, 0.0),
+        _ => (0.0, 0.0, 1.0),
+    };
+    let delta = time.delta_seconds();
+    let music_volume = settings.audio.music_volume();
+    music.base.fade_to(base, delta, music_volume, &audio);
+    music
+        .intensity
+        .fade_to(intensity, delta, music_volume, &audio);
+    music.calm.fade_to(calm, delta, music_volume, &audio);
+}
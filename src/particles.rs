@@ -0,0 +1,247 @@
+use crate::{
+    settings::Settings, theme::Theme, Food, FoodEatenEvent, Gate, GatePassedEvent, PlayerIndex,
+    PlayerLostEvent, PLAY_DIAGONAL_WEIGHTED,
+};
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+// the oldest particles are reused when more are needed
+const PARTICLE_POOL_SIZE: usize = 256;
+// velocity lost per second
+const PARTICLE_DRAG: f32 = 2.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParticleEffect {
+    pub count: u32,
+    // seconds
+    pub lifetime: f32,
+    // start speed and size, relative to the weighted play area diagonal
+    pub speed: f32,
+    pub size: f32,
+    // hex color, the color of the source is used if it is not set
+    pub color: Option<String>,
+}
+
+impl Default for ParticleEffect {
+    fn default() -> Self {
+        ParticleEffect {
+            count: 12,
+            lifetime: 0.5,
+            speed: 0.4,
+            size: 0.008,
+            color: None,
+        }
+    }
+}
+
+impl ParticleEffect {
+    fn color(&self, source_color: Color) -> Color {
+        let mut color = self
+            .color
+            .as_ref()
+            .and_then(|hex| Color::hex(hex.trim_start_matches('#')).ok())
+            .unwrap_or(source_color);
+        // translucent sources like gates would be invisible as particles
+        color.set_a(1.0);
+        color
+    }
+}
+
+// set count to 0 to disable an effect
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParticleSettings {
+    pub pickup: ParticleEffect,
+    pub gate: ParticleEffect,
+    pub death: ParticleEffect,
+}
+
+impl Default for ParticleSettings {
+    fn default() -> Self {
+        ParticleSettings {
+            pickup: ParticleEffect::default(),
+            // per edge of the gate
+            gate: ParticleEffect {
+                count: 8,
+                lifetime: 0.35,
+                speed: 0.5,
+                size: 0.005,
+                color: None,
+            },
+            death: ParticleEffect {
+                count: 48,
+                lifetime: 0.9,
+                speed: 0.6,
+                size: 0.012,
+                color: None,
+            },
+        }
+    }
+}
+
+// component
+pub struct Particle {
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+    size: f32,
+    color: Color,
+}
+
+// Resource: the particle entities, they are spawned once and hidden while unused
+pub struct ParticlePool {
+    particles: Vec<Entity>,
+    next: usize,
+}
+
+impl ParticlePool {
+    fn next(&mut self) -> Entity {
+        let entity = self.particles[self.next];
+        self.next = (self.next + 1) % self.particles.len();
+        entity
+    }
+}
+
+// every particle has its own material so it can fade out
+pub fn spawn_particle_pool(commands: &mut Commands, materials: &mut Assets<ColorMaterial>) {
+    let mut particles = Vec::with_capacity(PARTICLE_POOL_SIZE);
+    for _ in 0..PARTICLE_POOL_SIZE {
+        let entity = commands
+            .spawn_bundle(SpriteBundle {
+                material: materials.add(Color::NONE.into()),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: true,
+                },
+                ..Default::default()
+            })
+            .insert(Particle {
+                velocity: Vec2::ZERO,
+                age: 0.0,
+                lifetime: 0.0,
+                size: 0.0,
+                color: Color::NONE,
+            })
+            .id();
+        particles.push(entity);
+    }
+    commands.insert_resource(ParticlePool { particles, next: 0 });
+}
+
+fn emit(
+    pool: &mut ParticlePool,
+    query: &mut Query<(&mut Transform, &mut Particle, &mut Visible)>,
+    position: Vec2,
+    effect: &ParticleEffect,
+    color: Color,
+) {
+    let mut rng = rand::thread_rng();
+    for _ in 0..effect.count {
+        if let Ok((mut tf, mut particle, mut visible)) = query.get_mut(pool.next()) {
+            let angle = rng.gen_range(0.0..2.0 * PI);
+            let speed = effect.speed * PLAY_DIAGONAL_WEIGHTED * rng.gen_range(0.3..1.0);
+            tf.translation = position.extend(3.0);
+            *particle = Particle {
+                velocity: Vec2::new(angle.cos(), angle.sin()) * speed,
+                age: 0.0,
+                lifetime: effect.lifetime * rng.gen_range(0.6..1.0),
+                size: effect.size * PLAY_DIAGONAL_WEIGHTED,
+                color,
+            };
+            visible.is_visible = true;
+        }
+    }
+}
+
+pub fn emit_particles(
+    mut pool: ResMut<ParticlePool>,
+    settings: Res<Settings>,
+    theme: Res<Theme>,
+    mut ev_food_eaten: EventReader<FoodEatenEvent>,
+    mut ev_gate_passed: EventReader<GatePassedEvent>,
+    mut ev_player_lost: EventReader<PlayerLostEvent>,
+    mut query_set: QuerySet<(
+        Query<(&mut Transform, &mut Particle, &mut Visible)>,
+        Query<&Transform, With<Food>>,
+        Query<(&Transform, &Sprite), With<Gate>>,
+    )>,
+    player_query: Query<&PlayerIndex>,
+) {
+    let effects = &settings.particles;
+    // the events only carry the entity, the positions are read before the entities are despawned
+    let mut bursts = Vec::new();
+    for ev in ev_food_eaten.iter() {
+        if let Ok(tf) = query_set.q1().get(ev.food) {
+            bursts.push((tf.translation.truncate(), &effects.pickup, theme.food));
+        }
+    }
+    for ev in ev_gate_passed.iter() {
+        if let Ok((tf, sprite)) = query_set.q2().get(ev.gate) {
+            let edge = Vec2::new(sprite.size.x / 2.0, 0.0);
+            for position in [
+                tf.translation.truncate() - edge,
+                tf.translation.truncate() + edge,
+            ]
+            .iter()
+            {
+                bursts.push((*position, &effects.gate, theme.gate));
+            }
+        }
+    }
+    // the debris has the color of the player
+    for ev in ev_player_lost.iter() {
+        let player_index = player_query.get(ev.player).map_or(0, |index| index.0);
+        bursts.push((
+            ev.position,
+            &effects.death,
+            theme.player_color(player_index),
+        ));
+    }
+    for (position, effect, source_color) in bursts {
+        emit(
+            &mut pool,
+            query_set.q0_mut(),
+            position,
+            effect,
+            effect.color(source_color),
+        );
+    }
+}
+
+pub fn update_particles(
+    time: Res<Time>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query: Query<(
+        &mut Transform,
+        &mut Particle,
+        &mut Visible,
+        &mut Sprite,
+        &Handle<ColorMaterial>,
+    )>,
+) {
+    let delta = time.delta_seconds();
+    for (mut tf, mut particle, mut visible, mut sprite, material) in query.iter_mut() {
+        if !visible.is_visible {
+            continue;
+        }
+        particle.age += delta;
+        if particle.age >= particle.lifetime {
+            visible.is_visible = false;
+            continue;
+        }
+        let velocity = particle.velocity;
+        tf.translation += (velocity * delta).extend(0.0);
+        particle.velocity *= (-PARTICLE_DRAG * delta).exp();
+        // shrink and fade out
+        let life_left = 1.0 - particle.age / particle.lifetime;
+        sprite.size = Vec2::splat(particle.size * (0.5 + 0.5 * life_left));
+        if let Some(material) = materials.get_mut(material) {
+            let mut color = particle.color;
+            color.set_a(particle.color.a() * life_left);
+            material.color = color;
+        }
+    }
+}
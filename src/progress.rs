@@ -0,0 +1,65 @@
+use crate::{stats::DeathCause, storage};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const PROGRESS_FILE: &str = "progress.ron";
+
+// Resource: what the player has reached so far, stored in save/progress.ron
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Progress {
+    pub best_score: i32,
+    // ids of the unlocked achievements
+    pub achievements: Vec<String>,
+    // lifetime stats of the single player runs
+    pub runs_played: u32,
+    pub total_score: i64,
+    pub total_food: u32,
+    pub total_gates: u32,
+    // in seconds
+    pub total_play_time: f64,
+    pub longest_run: f32,
+    pub deaths_by_cause: BTreeMap<DeathCause, u32>,
+}
+
+impl Progress {
+    pub fn load() -> Self {
+        storage::load_or_default(PROGRESS_FILE)
+    }
+
+    pub fn save(&self) {
+        storage::save(PROGRESS_FILE, self);
+    }
+
+    pub fn deaths(&self) -> u32 {
+        self.deaths_by_cause.values().sum()
+    }
+
+    pub fn average_score(&self) -> f64 {
+        if self.runs_played == 0 {
+            0.0
+        } else {
+            self.total_score as f64 / self.runs_played as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deaths_add_up_every_cause() {
+        let mut progress = Progress::default();
+        progress.deaths_by_cause.insert(DeathCause::Wall, 4);
+        progress.deaths_by_cause.insert(DeathCause::FellBehind, 2);
+        progress.runs_played = 6;
+        progress.total_score = 30;
+        assert_eq!(progress.deaths(), 6);
+        assert_eq!(progress.average_score(), 5.0);
+
+        let content = ron::ser::to_string(&progress).unwrap();
+        let loaded: Progress = ron::de::from_str(&content).unwrap();
+        assert_eq!(loaded.deaths_by_cause, progress.deaths_by_cause);
+    }
+}
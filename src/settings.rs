@@ -0,0 +1,98 @@
+use crate::{
+    audio::AudioSettings, bot::BotSkill, camera::CameraSettings, colorblind::ColorblindMode,
+    leaderboard::LeaderboardSettings, particles::ParticleSettings, storage,
+};
+use serde::{Deserialize, Serialize};
+
+const SETTINGS_FILE: &str = "settings.ron";
+
+// Resource: user settings, stored in save/settings.ron
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // pushes cost stamina, which regenerates over time
+    pub stamina: bool,
+    pub camera: CameraSettings,
+    // name of a file in assets/themes, T switches between the built-in themes
+    pub theme: String,
+    // palette and shapes for colorblind players, C switches between the modes
+    pub colorblind: ColorblindMode,
+    // lifetime, count, speed, size and color of every particle effect
+    pub particles: ParticleSettings,
+    // turns off the player trail, camera shake and zoom
+    pub reduced_motion: bool,
+    // name of the selected player skin
+    pub skin: String,
+    // master, music and sound effect volumes, M mutes
+    pub audio: AudioSettings,
+    // server url and player name for the online leaderboard
+    pub leaderboard: LeaderboardSettings,
+    // passing a gate in the versus mode sends a wall to the opponent, both sides need it
+    pub versus_attacks: bool,
+    // bot skill per player index, None plays by hand, e.g. [Some(Hard), None]
+    pub autopilot: Vec<Option<BotSkill>>,
+    // the skill of the first player from `--autopilot`, it isn't saved
+    #[serde(skip)]
+    pub autopilot_override: Option<BotSkill>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            stamina: false,
+            camera: CameraSettings::default(),
+            theme: "default".to_string(),
+            colorblind: ColorblindMode::Off,
+            particles: ParticleSettings::default(),
+            reduced_motion: false,
+            skin: "Classic".to_string(),
+            audio: AudioSettings::default(),
+            leaderboard: LeaderboardSettings::default(),
+            versus_attacks: true,
+            autopilot: Vec::new(),
+            autopilot_override: None,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        let settings: Settings = storage::load_or_default(SETTINGS_FILE);
+        // write back so missing fields show up in the file with their defaults
+        settings.save();
+        settings
+    }
+
+    pub fn save(&self) {
+        storage::save(SETTINGS_FILE, self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_get_their_defaults() {
+        let settings: Settings =
+            ron::de::from_str("(stamina: true, leaderboard: (name: \"Ann\"))").unwrap();
+        assert!(settings.stamina);
+        assert_eq!(settings.theme, "default");
+        assert_eq!(settings.skin, "Classic");
+        assert!(settings.versus_attacks);
+        assert!(settings.autopilot.is_empty());
+        assert_eq!(settings.leaderboard.name, "Ann");
+        // the leaderboard stays off until a url is set
+        assert!(settings.leaderboard.url.is_empty());
+    }
+
+    #[test]
+    fn autopilot_override_is_not_saved() {
+        let mut settings = Settings::default();
+        settings.autopilot_override = Some(BotSkill::Hard);
+        let content = ron::ser::to_string(&settings).unwrap();
+        assert!(!content.contains("autopilot_override"));
+        let loaded: Settings = ron::de::from_str(&content).unwrap();
+        assert_eq!(loaded.autopilot_override, None);
+    }
+}
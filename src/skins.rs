@@ -0,0 +1,132 @@
+use crate::{progress::Progress, settings::Settings, theme::Theme};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlayerShape {
+    Square,
+    Round,
+    Diamond,
+}
+
+impl Default for PlayerShape {
+    fn default() -> Self {
+        PlayerShape::Square
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailStyle {
+    Fade,
+    // small dots with gaps in between
+    Dots,
+    // twice as long as the normal trail
+    Long,
+    Off,
+}
+
+pub enum Unlock {
+    Always,
+    BestScore(i32),
+}
+
+pub struct Skin {
+    pub name: &'static str,
+    // hex color, the theme's player color is used if it is not set
+    color: Option<&'static str>,
+    pub shape: PlayerShape,
+    pub trail: TrailStyle,
+    unlock: Unlock,
+}
+
+pub static SKINS: [Skin; 6] = [
+    Skin {
+        name: "Classic",
+        color: None,
+        shape: PlayerShape::Square,
+        trail: TrailStyle::Fade,
+        unlock: Unlock::Always,
+    },
+    Skin {
+        name: "Mint",
+        color: Some("34D399"),
+        shape: PlayerShape::Round,
+        trail: TrailStyle::Dots,
+        unlock: Unlock::Always,
+    },
+    Skin {
+        name: "Ruby",
+        color: Some("EF4444"),
+        shape: PlayerShape::Diamond,
+        trail: TrailStyle::Fade,
+        unlock: Unlock::BestScore(10),
+    },
+    Skin {
+        name: "Violet",
+        color: Some("A78BFA"),
+        shape: PlayerShape::Round,
+        trail: TrailStyle::Long,
+        unlock: Unlock::BestScore(20),
+    },
+    Skin {
+        name: "Gold",
+        color: Some("FBBF24"),
+        shape: PlayerShape::Diamond,
+        trail: TrailStyle::Long,
+        unlock: Unlock::BestScore(35),
+    },
+    Skin {
+        name: "Ghost",
+        color: Some("F9FAFBB0"),
+        shape: PlayerShape::Square,
+        trail: TrailStyle::Off,
+        unlock: Unlock::BestScore(50),
+    },
+];
+
+impl Skin {
+    pub fn is_unlocked(&self, progress: &Progress) -> bool {
+        match self.unlock {
+            Unlock::Always => true,
+            Unlock::BestScore(score) => progress.best_score >= score,
+        }
+    }
+
+    pub fn unlock_hint(&self) -> String {
+        match self.unlock {
+            Unlock::Always => String::new(),
+            Unlock::BestScore(score) => format!("Reach a score of {} to unlock", score),
+        }
+    }
+
+    // the colorblind palettes keep their player color
+    pub fn apply(&self, theme: &mut Theme) {
+        if let Some(color) = self.color {
+            if !theme.shapes {
+                theme.player = Color::hex(color).unwrap();
+            }
+        }
+        theme.player_shape = self.shape;
+    }
+}
+
+pub fn skin_index(name: &str) -> usize {
+    SKINS.iter().position(|skin| skin.name == name).unwrap_or(0)
+}
+
+pub fn selected_skin(settings: &Settings) -> &'static Skin {
+    &SKINS[skin_index(&settings.skin)]
+}
+
+// a skin can only be selected by editing the settings file while it is locked
+pub fn check_selected_skin(
+    mut settings: ResMut<Settings>,
+    progress: Res<Progress>,
+    mut theme: ResMut<Theme>,
+) {
+    if !selected_skin(&settings).is_unlocked(&progress) {
+        settings.skin = SKINS[0].name.to_string();
+        settings.save();
+        *theme = crate::theme::load_theme(&settings);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{
+    game_assets::GameAssets,
+    lanes::{GameMode, Lane, LaneViews},
+    stats::DeathCause,
+    Food, Gate, Player, PlayerLostEvent, Wall, PLAY_WIDTH,
+};
+use bevy::prelude::*;
+
+// one entity in the snapshot, in world units relative to the bottom left corner of the view
+struct SnapshotRect {
+    position: Vec2,
+    size: Vec2,
+    material: Handle<ColorMaterial>,
+    // the entity that was involved in the crash
+    involved: bool,
+}
+
+// Resource: the final moment of the last run
+pub struct DeathSnapshot {
+    pub cause: DeathCause,
+    view_size: Vec2,
+    rects: Vec<SnapshotRect>,
+    // where the player died
+    position: Vec2,
+}
+
+// remembers where everything was when the player crashed, races show their result instead
+pub fn take_snapshot(
+    mut commands: Commands,
+    mut ev_player_lost: EventReader<PlayerLostEvent>,
+    mode: Res<GameMode>,
+    lane_views: Res<LaneViews>,
+    lane_query: Query<&Lane>,
+    query: Query<
+        (Entity, &Transform, &Sprite, &Handle<ColorMaterial>),
+        Or<(With<Player>, With<Wall>, With<Gate>, With<Food>)>,
+    >,
+) {
+    // a single run has one player, so there is one event at most
+    let ev = match ev_player_lost.iter().next() {
+        Some(ev) if *mode == GameMode::Single => ev,
+        _ => return,
+    };
+    let lane = lane_query.get(ev.player).copied().unwrap_or(Lane(0));
+    let view = lane_views.get(&lane);
+    let view_size = Vec2::new(PLAY_WIDTH, 2.0 * view.half_height);
+    let view_min = Vec2::new(
+        lane.x() - PLAY_WIDTH / 2.0,
+        view.camera_y - view.half_height,
+    );
+    let mut rects = Vec::new();
+    for (entity, tf, sprite, material) in query.iter() {
+        // only the part inside of the view
+        let min = (tf.translation.truncate() - sprite.size / 2.0 - view_min).max(Vec2::ZERO);
+        let max = (tf.translation.truncate() + sprite.size / 2.0 - view_min).min(view_size);
+        if min.x >= max.x || min.y >= max.y {
+            continue;
+        }
+        rects.push(SnapshotRect {
+            position: min,
+            size: max - min,
+            material: material.clone(),
+            involved: ev.entity == Some(entity),
+        });
+    }
+    commands.insert_resource(DeathSnapshot {
+        cause: ev.cause,
+        view_size,
+        rects,
+        position: (ev.position - view_min).clamp(Vec2::ZERO, view_size),
+    });
+}
+
+// a small frozen picture of the view, height in pixels
+pub fn spawn_snapshot(
+    parent: &mut ChildBuilder,
+    snapshot: &DeathSnapshot,
+    game_assets: &GameAssets,
+    height: f32,
+) {
+    let scale = height / snapshot.view_size.y;
+    let node = |position: Vec2, size: Vec2, material: Handle<ColorMaterial>| NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Px(position.x * scale),
+                bottom: Val::Px(position.y * scale),
+                ..Default::default()
+            },
+            size: Size::new(Val::Px(size.x * scale), Val::Px(size.y * scale)),
+            ..Default::default()
+        },
+        material,
+        ..Default::default()
+    };
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(
+                    Val::Px(snapshot.view_size.x * scale),
+                    Val::Px(snapshot.view_size.y * scale),
+                ),
+                ..Default::default()
+            },
+            material: game_assets.letterbox_material.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            for rect in snapshot.rects.iter() {
+                // the wall that was hit gets a highlight around it
+                if rect.involved {
+                    let margin = Vec2::splat(0.01 * snapshot.view_size.y);
+                    parent.spawn_bundle(node(
+                        rect.position - margin,
+                        rect.size + 2.0 * margin,
+                        game_assets.stamina_bar_material.clone(),
+                    ));
+                }
+                parent.spawn_bundle(node(rect.position, rect.size, rect.material.clone()));
+            }
+            // marks where the player was
+            let marker_size = Vec2::splat(0.06 * snapshot.view_size.y);
+            parent.spawn_bundle(node(
+                snapshot.position - marker_size / 2.0,
+                marker_size,
+                game_assets.gesture_line_material.clone(),
+            ));
+        });
+}
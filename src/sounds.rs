@@ -0,0 +1,177 @@
+use crate::{
+    audio::SfxChannels,
+    game_assets::GameAssets,
+    lanes::{GameMode, Lane},
+    Crashed, Food, FoodEatenEvent, Gate, GatePassedEvent, Player, PlayerLostEvent,
+    PlayerPushedEvent, Wall, PLAY_DIAGONAL_WEIGHTED, PLAY_WIDTH,
+};
+use bevy::{prelude::*, sprite::collide_aabb::collide};
+use bevy_kira_audio::Audio;
+
+// pickups within this time after the last one continue the chain
+const CHAIN_WINDOW: f32 = 1.5;
+// playback rate added per pickup in a chain
+const CHAIN_PITCH_STEP: f32 = 0.08;
+const CHAIN_MAX_PITCH: f32 = 1.8;
+// distance between the player and a wall that counts as a near miss, relative to the weighted play diagonal
+const NEAR_MISS_DISTANCE: f32 = 0.03;
+
+// consecutive food pickups
+#[derive(Default)]
+pub struct PickupChain {
+    count: u32,
+    since_last: f32,
+}
+
+// component: the player came close to this wall, true once the near miss sound was played
+pub struct NearMiss(bool);
+
+// the x in the play area of the lane, in split screen every lane gets its half of the stereo field
+fn pan_x(x: f32, lane: &Lane, mode: GameMode) -> f32 {
+    let x = x - lane.x();
+    match (mode, lane.0) {
+        (GameMode::Single, _) | (GameMode::Coop, _) | (GameMode::Versus, _) => x,
+        (GameMode::SplitScreen, 0) => (x - PLAY_WIDTH / 2.0) / 2.0,
+        (GameMode::SplitScreen, _) => (x + PLAY_WIDTH / 2.0) / 2.0,
+    }
+}
+
+// one sound per event, so several events in one frame play several sounds.
+// every sound is panned by the position of its source, which is read before it is despawned
+pub fn play_event_sounds(
+    audio: Res<Audio>,
+    mut channels: ResMut<SfxChannels>,
+    game_assets: Res<GameAssets>,
+    mode: Res<GameMode>,
+    time: Res<Time>,
+    mut chain: Local<PickupChain>,
+    mut ev_food_eaten: EventReader<FoodEatenEvent>,
+    mut ev_gate_passed: EventReader<GatePassedEvent>,
+    mut ev_player_pushed: EventReader<PlayerPushedEvent>,
+    mut ev_player_lost: EventReader<PlayerLostEvent>,
+    source_query: Query<&Transform, Or<(With<Food>, With<Gate>, With<Wall>)>>,
+    player_query: Query<(&Transform, &Lane), With<Player>>,
+) {
+    // the x of the source if it still exists, or of the player, panned within the lane of the player
+    let source_x = |source: Option<Entity>, player: Entity| {
+        let (player_tf, lane) = match player_query.get(player) {
+            Ok(player) => player,
+            Err(_) => return 0.0,
+        };
+        let x = source
+            .and_then(|source| source_query.get(source).ok())
+            .map_or(player_tf.translation.x, |tf| tf.translation.x);
+        pan_x(x, lane, *mode)
+    };
+    chain.since_last += time.delta_seconds();
+    for ev in ev_food_eaten.iter() {
+        if chain.since_last > CHAIN_WINDOW {
+            chain.count = 0;
+        }
+        chain.since_last = 0.0;
+        let pitch = (1.0 + CHAIN_PITCH_STEP * chain.count as f32).min(CHAIN_MAX_PITCH);
+        chain.count += 1;
+        let x = source_x(Some(ev.food), ev.player);
+        channels.play_at(&audio, game_assets.food_sound.clone(), x, pitch);
+    }
+    for ev in ev_gate_passed.iter() {
+        let x = source_x(Some(ev.gate), ev.player);
+        channels.play_at(&audio, game_assets.gate_sound.clone(), x, 1.0);
+    }
+    for ev in ev_player_pushed.iter() {
+        let x = source_x(None, ev.player);
+        channels.play_at(&audio, game_assets.push_sound.clone(), x, 1.0);
+    }
+    for ev in ev_player_lost.iter() {
+        let x = match player_query.get(ev.player) {
+            Ok((_, lane)) => {
+                let x = ev
+                    .entity
+                    .and_then(|entity| source_query.get(entity).ok())
+                    .map_or(ev.position.x, |tf| tf.translation.x);
+                pan_x(x, lane, *mode)
+            }
+            Err(_) => 0.0,
+        };
+        channels.play_at(&audio, game_assets.death_sound.clone(), x, 1.0);
+    }
+}
+
+// plays a sound once a player got past a wall it came close to
+pub fn check_near_misses(
+    mut commands: Commands,
+    audio: Res<Audio>,
+    mut channels: ResMut<SfxChannels>,
+    game_assets: Res<GameAssets>,
+    mode: Res<GameMode>,
+    player_query: Query<(&Transform, &Sprite, &Lane), (With<Player>, Without<Crashed>)>,
+    mut wall_query: Query<
+        (Entity, &Transform, &Sprite, &Lane, Option<&mut NearMiss>),
+        (With<Wall>, Without<Player>),
+    >,
+) {
+    for (player_tf, player_sprite, player_lane) in player_query.iter() {
+        let near_size =
+            player_sprite.size + Vec2::splat(2.0 * NEAR_MISS_DISTANCE * PLAY_DIAGONAL_WEIGHTED);
+        // x of the wall that was passed
+        let mut play = None;
+        for (entity, wall_tf, wall_sprite, wall_lane, near_miss) in wall_query.iter_mut() {
+            if wall_lane != player_lane {
+                continue;
+            }
+            match near_miss {
+                None => {
+                    let is_near = collide(
+                        player_tf.translation,
+                        near_size,
+                        wall_tf.translation,
+                        wall_sprite.size,
+                    )
+                    .is_some();
+                    // touching the wall is a crash, not a near miss
+                    let is_hit = collide(
+                        player_tf.translation,
+                        player_sprite.size,
+                        wall_tf.translation,
+                        wall_sprite.size,
+                    )
+                    .is_some();
+                    if is_near && !is_hit {
+                        commands.entity(entity).insert(NearMiss(false));
+                    }
+                }
+                Some(mut near_miss) => {
+                    let player_bottom = player_tf.translation.y - player_sprite.size.y / 2.0;
+                    let wall_top = wall_tf.translation.y + wall_sprite.size.y / 2.0;
+                    if !near_miss.0 && player_bottom > wall_top {
+                        near_miss.0 = true;
+                        play = Some(wall_tf.translation.x);
+                    }
+                }
+            }
+        }
+        // both halves of a wall can be passed closely at the same time
+        if let Some(x) = play {
+            let x = pan_x(x, player_lane, *mode);
+            channels.play_at(&audio, game_assets.near_miss_sound.clone(), x, 1.0);
+        }
+    }
+}
+
+// runs in every state, the game assets are loaded after the first frame
+pub fn click_sounds(
+    audio: Res<Audio>,
+    channels: Res<SfxChannels>,
+    game_assets: Option<Res<GameAssets>>,
+    query: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+) {
+    let game_assets = match game_assets {
+        Some(game_assets) => game_assets,
+        None => return,
+    };
+    for interaction in query.iter() {
+        if *interaction == Interaction::Clicked {
+            audio.play_in_channel(game_assets.click_sound.clone(), &channels.effects);
+        }
+    }
+}
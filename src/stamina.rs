@@ -0,0 +1,120 @@
+use crate::{
+    game_assets::GameAssets,
+    lanes::{Lane, LaneViews},
+    PLAY_DIAGONAL_WEIGHTED, PLAY_HEIGHT,
+};
+use bevy::prelude::*;
+
+const STAMINA_MAX: f32 = 1.0;
+// stamina regenerated per second
+const STAMINA_REGEN: f32 = 0.3;
+// a drag of this length (relative to the weighted play area diagonal) costs the full bar
+const STAMINA_FULL_PUSH: f32 = 0.5;
+const STAMINA_BAR_WIDTH: f32 = 0.3;
+const STAMINA_BAR_HEIGHT: f32 = 0.012;
+
+// component of the player
+pub struct Stamina {
+    pub enabled: bool,
+    pub current: f32,
+}
+
+impl Stamina {
+    pub fn new(enabled: bool) -> Self {
+        Stamina {
+            enabled,
+            current: STAMINA_MAX,
+        }
+    }
+
+    // pays for a push and returns how much of it could be afforded (0.0 - 1.0)
+    pub fn spend(&mut self, drag_length: f32) -> f32 {
+        if !self.enabled {
+            return 1.0;
+        }
+        let cost = drag_length / (STAMINA_FULL_PUSH * PLAY_DIAGONAL_WEIGHTED);
+        if cost <= self.current {
+            self.current -= cost;
+            1.0
+        } else {
+            let factor = self.current / cost;
+            self.current = 0.0;
+            factor
+        }
+    }
+}
+
+// components, the row counts the bars of a lane from the top
+pub struct StaminaBar {
+    player: Entity,
+    row: usize,
+}
+pub struct StaminaBarBackground {
+    row: usize,
+}
+
+pub fn spawn_stamina_bar(
+    commands: &mut Commands,
+    game_assets: &GameAssets,
+    player: Entity,
+    lane: Lane,
+    row: usize,
+) {
+    let size = Vec2::new(
+        STAMINA_BAR_WIDTH * PLAY_DIAGONAL_WEIGHTED,
+        STAMINA_BAR_HEIGHT * PLAY_DIAGONAL_WEIGHTED,
+    );
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: game_assets.stamina_bar_background_material.clone(),
+            sprite: Sprite::new(size),
+            transform: Transform::from_xyz(lane.x(), 0.0, 2.0),
+            ..Default::default()
+        })
+        .insert(StaminaBarBackground { row })
+        .insert(lane);
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: game_assets.stamina_bar_material.clone(),
+            sprite: Sprite::new(size),
+            transform: Transform::from_xyz(lane.x(), 0.0, 2.1),
+            ..Default::default()
+        })
+        .insert(StaminaBar { player, row })
+        .insert(lane);
+}
+
+pub fn regenerate_stamina(mut query: Query<&mut Stamina>, time: Res<Time>) {
+    for mut stamina in query.iter_mut() {
+        stamina.current = (stamina.current + STAMINA_REGEN * time.delta_seconds()).min(STAMINA_MAX);
+    }
+}
+
+// keeps the bars at the top of the view of their lane, the fill shrinks to the left
+pub fn update_stamina_bar(
+    lane_views: Res<LaneViews>,
+    stamina_query: Query<&Stamina>,
+    mut bar_query: Query<(&mut Transform, &mut Sprite, &StaminaBar, &Lane)>,
+    mut background_query: Query<
+        (&mut Transform, &StaminaBarBackground, &Lane),
+        Without<StaminaBar>,
+    >,
+) {
+    let full_width = STAMINA_BAR_WIDTH * PLAY_DIAGONAL_WEIGHTED;
+    let height = STAMINA_BAR_HEIGHT * PLAY_DIAGONAL_WEIGHTED;
+    let bar_y = |lane: &Lane, row: usize| {
+        lane_views.get(lane).camera_y + PLAY_HEIGHT / 2.0 - (2.0 + 2.0 * row as f32) * height
+    };
+    for (mut tf, background, lane) in background_query.iter_mut() {
+        tf.translation.y = bar_y(lane, background.row);
+    }
+    for (mut tf, mut sprite, bar, lane) in bar_query.iter_mut() {
+        let current = stamina_query
+            .get(bar.player)
+            .map_or(0.0, |stamina| stamina.current);
+        let width = full_width * current / STAMINA_MAX;
+        sprite.size = Vec2::new(width, height);
+        tf.translation.x = lane.x() + (width - full_width) / 2.0;
+        tf.translation.y = bar_y(lane, bar.row);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{
+    bot,
+    game_assets::GameAssets,
+    lanes::GameMode,
+    leaderboard::LeaderboardText,
+    menu,
+    progress::Progress,
+    settings::Settings,
+    theme::{Theme, ThemeText},
+    FoodEatenEvent, GatePassedEvent, Player, PlayerLostEvent, Score, WindowSizeDiagonalWeighted,
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DeathCause {
+    Wall,
+    // the player reached the bottom of the view
+    FellBehind,
+}
+
+impl DeathCause {
+    pub fn description(&self) -> &'static str {
+        match self {
+            DeathCause::Wall => "Hit a wall",
+            DeathCause::FellBehind => "Fell behind",
+        }
+    }
+}
+
+// Resource: what happened in the current run
+#[derive(Default)]
+pub struct RunStats {
+    pub time: f32,
+    pub score: i32,
+    pub food_eaten: u32,
+    pub gates_passed: u32,
+    pub gates_since_food: u32,
+    pub death_cause: Option<DeathCause>,
+}
+
+// updates the run stats from the game events, races are not tracked
+pub fn track_run(
+    mode: Res<GameMode>,
+    settings: Res<Settings>,
+    mut run_stats: ResMut<RunStats>,
+    time: Res<Time>,
+    mut ev_food_eaten: EventReader<FoodEatenEvent>,
+    mut ev_gate_passed: EventReader<GatePassedEvent>,
+    mut ev_player_lost: EventReader<PlayerLostEvent>,
+    score_query: Query<&Score, With<Player>>,
+) {
+    if *mode != GameMode::Single || bot::skill_of(&settings, 0).is_some() {
+        return;
+    }
+    run_stats.time += time.delta_seconds();
+    for _ in ev_food_eaten.iter() {
+        run_stats.food_eaten += 1;
+        run_stats.gates_since_food = 0;
+    }
+    for _ in ev_gate_passed.iter() {
+        run_stats.gates_passed += 1;
+        run_stats.gates_since_food += 1;
+    }
+    if let Ok(score) = score_query.single() {
+        run_stats.score = score.0;
+    }
+    for ev in ev_player_lost.iter() {
+        run_stats.death_cause = Some(ev.cause);
+    }
+}
+
+// adds the finished run to the lifetime stats
+pub fn record_run(
+    mode: Res<GameMode>,
+    settings: Res<Settings>,
+    run_stats: Res<RunStats>,
+    mut progress: ResMut<Progress>,
+) {
+    if *mode != GameMode::Single || bot::skill_of(&settings, 0).is_some() {
+        return;
+    }
+    progress.runs_played += 1;
+    progress.total_score += run_stats.score as i64;
+    progress.best_score = progress.best_score.max(run_stats.score);
+    progress.total_food += run_stats.food_eaten;
+    progress.total_gates += run_stats.gates_passed;
+    progress.total_play_time += run_stats.time as f64;
+    progress.longest_run = progress.longest_run.max(run_stats.time);
+    if let Some(cause) = run_stats.death_cause {
+        *progress.deaths_by_cause.entry(cause).or_insert(0) += 1;
+    }
+    progress.save();
+}
+
+// 1h 02m 03s
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else {
+        format!("{}m {:02}s", minutes, seconds)
+    }
+}
+
+pub fn spawn_stats_screen(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    theme: Res<Theme>,
+    progress: Res<Progress>,
+    window_size_diag: Res<WindowSizeDiagonalWeighted>,
+) {
+    let mut lines = vec![
+        format!("Runs played {}", progress.runs_played),
+        format!("Best score {}", progress.best_score),
+        format!("Average score {:.1}", progress.average_score()),
+        format!("Food eaten {}", progress.total_food),
+        format!("Gates passed {}", progress.total_gates),
+        format!("Play time {}", format_duration(progress.total_play_time)),
+        format!(
+            "Longest run {}",
+            format_duration(progress.longest_run as f64)
+        ),
+        format!("Deaths {}", progress.deaths()),
+    ];
+    for (cause, count) in progress.deaths_by_cause.iter() {
+        lines.push(format!("{}: {}", cause.description(), count));
+    }
+    // filled in by the leaderboard once it answered
+    lines.push(String::new());
+    let lines = lines
+        .into_iter()
+        .map(|line| (line, ThemeText::Secondary))
+        .collect();
+    let line_entities = menu::spawn_list_screen(
+        &mut commands,
+        &game_assets,
+        &theme,
+        window_size_diag.0,
+        "Stats",
+        lines,
+    );
+    if let Some(leaderboard_line) = line_entities.last() {
+        commands.entity(*leaderboard_line).insert(LeaderboardText);
+    }
+}
@@ -0,0 +1,35 @@
+// small helpers to persist data between sessions as ron files in the save folder
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fs, path::PathBuf};
+
+const SAVE_DIR: &str = "save";
+
+fn save_path(file_name: &str) -> PathBuf {
+    PathBuf::from(SAVE_DIR).join(file_name)
+}
+
+// loads a file from the save folder, falls back to the default if it is missing or broken
+pub fn load_or_default<T: DeserializeOwned + Default>(file_name: &str) -> T {
+    let path = save_path(file_name);
+    match fs::read_to_string(&path) {
+        Ok(content) => ron::de::from_str(&content).unwrap_or_else(|err| {
+            eprintln!("Could not parse {}: {}", path.display(), err);
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+pub fn save<T: Serialize>(file_name: &str, value: &T) {
+    let path = save_path(file_name);
+    let content = match ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()) {
+        Ok(content) => content,
+        Err(err) => {
+            eprintln!("Could not serialize {}: {}", path.display(), err);
+            return;
+        }
+    };
+    if let Err(err) = fs::create_dir_all(SAVE_DIR).and_then(|_| fs::write(&path, content)) {
+        eprintln!("Could not write {}: {}", path.display(), err);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{
+    game_assets::{self, GameAssets},
+    settings::Settings,
+    skins::{self, PlayerShape},
+};
+use bevy::prelude::*;
+use serde::{de, Deserialize, Deserializer};
+use std::fs;
+
+const THEME_DIR: &str = "themes";
+// the themes that can be switched through with T
+pub const THEMES: [&str; 3] = ["default", "dark", "high_contrast"];
+
+// Resource: every color of the game, loaded from assets/themes/<name>.ron
+// colors are hex strings: "RRGGBB" or "RRGGBBAA", missing colors come from the default theme
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    #[serde(deserialize_with = "hex_color")]
+    pub background: Color,
+    #[serde(deserialize_with = "hex_color")]
+    pub player: Color,
+    // the second player in co-op
+    #[serde(deserialize_with = "hex_color")]
+    pub player_two: Color,
+    #[serde(deserialize_with = "hex_color")]
+    pub food: Color,
+    #[serde(deserialize_with = "hex_color")]
+    pub wall: Color,
+    #[serde(deserialize_with = "hex_color")]
+    pub gate: Color,
+    #[serde(deserialize_with = "hex_color")]
+    pub gesture_line: Color,
+    #[serde(deserialize_with = "hex_color")]
+    pub letterbox: Color,
+    #[serde(deserialize_with = "hex_color")]
+    pub stamina_bar: Color,
+    #[serde(deserialize_with = "hex_color")]
+    pub stamina_bar_background: Color,
+    #[serde(deserialize_with = "hex_color")]
+    pub ui_background: Color,
+    #[serde(deserialize_with = "hex_color")]
+    pub text: Color,
+    #[serde(deserialize_with = "hex_color")]
+    pub text_secondary: Color,
+    #[serde(deserialize_with = "hex_color")]
+    pub button_normal: Color,
+    #[serde(deserialize_with = "hex_color")]
+    pub button_hovered: Color,
+    #[serde(deserialize_with = "hex_color")]
+    pub button_pressed: Color,
+    // parallax stars in the background, the far layers are dimmer
+    #[serde(deserialize_with = "hex_color")]
+    pub stars: Color,
+    // 1.0 is the normal amount of stars, 0.0 turns them off
+    pub star_density: f32,
+    // food and gates get their own shapes, set by the colorblind mode
+    #[serde(skip)]
+    pub shapes: bool,
+    // set by the selected skin
+    #[serde(skip)]
+    pub player_shape: PlayerShape,
+}
+
+impl Default for Theme {
+    // same as assets/themes/default.ron, used if a theme file can't be loaded
+    fn default() -> Self {
+        Theme {
+            background: Color::rgb_u8(52, 103, 235),
+            player: Color::rgb_u8(235, 107, 52),
+            player_two: Color::rgb_u8(0xEC, 0x48, 0x99),
+            food: Color::rgb_u8(52, 235, 140),
+            wall: Color::rgb_u8(0xE5, 0xE7, 0xEB),
+            gate: Color::rgba_u8(0x10, 0xB9, 0x81, 64),
+            gesture_line: Color::rgba_u8(0xD1, 0xD5, 0xDB, 0x80),
+            letterbox: Color::rgb_u8(0x11, 0x18, 0x27),
+            stamina_bar: Color::rgb_u8(0xFB, 0xBF, 0x24),
+            stamina_bar_background: Color::rgba(0.0, 0.0, 0.0, 0.25),
+            ui_background: Color::rgb_u8(0x37, 0x41, 0x51),
+            text: Color::rgb(1.0, 1.0, 1.0),
+            text_secondary: Color::rgb_u8(0xD1, 0xD5, 0xDB),
+            button_normal: Color::rgba_u8(0x37, 0x41, 0x51, 255),
+            button_hovered: Color::rgba(0.0, 0.0, 0.0, 0.25),
+            button_pressed: Color::rgba_u8(0x37, 0x41, 0x51, 10),
+            stars: Color::rgba_u8(0xFF, 0xFF, 0xFF, 0x80),
+            star_density: 1.0,
+            shapes: false,
+            player_shape: PlayerShape::Square,
+        }
+    }
+}
+
+impl Theme {
+    // the first player and the second player in split screen and co-op
+    pub fn player_color(&self, player_index: usize) -> Color {
+        if player_index == 0 {
+            self.player
+        } else {
+            self.player_two
+        }
+    }
+
+    pub fn load(name: &str) -> Self {
+        // same folder as the other assets, not relative to the working directory
+        let path = game_assets::asset_path(&format!("{}/{}.ron", THEME_DIR, name));
+        let theme = fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|content| ron::de::from_str(&content).map_err(|err| err.to_string()));
+        match theme {
+            Ok(theme) => theme,
+            // the first theme is loaded before the log is set up
+            Err(err) => {
+                eprintln!("Could not load theme {}: {}", path.display(), err);
+                Theme::default()
+            }
+        }
+    }
+}
+
+// the theme of the settings with the colorblind mode and the skin applied
+pub fn load_theme(settings: &Settings) -> Theme {
+    let mut theme = Theme::load(&settings.theme);
+    settings.colorblind.apply(&mut theme);
+    skins::selected_skin(settings).apply(&mut theme);
+    theme
+}
+
+fn hex_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    Color::hex(hex.trim_start_matches('#'))
+        .map_err(|err| de::Error::custom(format!("invalid color {}: {:?}", hex, err)))
+}
+
+// component: text that takes its color from the theme
+pub enum ThemeText {
+    Primary,
+    Secondary,
+}
+
+impl ThemeText {
+    pub fn color(&self, theme: &Theme) -> Color {
+        match self {
+            ThemeText::Primary => theme.text,
+            ThemeText::Secondary => theme.text_secondary,
+        }
+    }
+}
+
+// T switches to the next theme
+pub fn switch_theme(
+    keyboard: Res<Input<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut theme: ResMut<Theme>,
+) {
+    if keyboard.just_pressed(KeyCode::T) {
+        let next = THEMES
+            .iter()
+            .position(|name| *name == settings.theme)
+            .map_or(0, |index| (index + 1) % THEMES.len());
+        settings.theme = THEMES[next].to_string();
+        settings.save();
+        *theme = load_theme(&settings);
+    }
+}
+
+// the entities share one material per role, so recoloring the materials recolors everything
+pub fn apply_theme(
+    theme: Res<Theme>,
+    game_assets: Option<Res<GameAssets>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut clear_color: ResMut<ClearColor>,
+    mut text_query: Query<(&mut Text, &ThemeText)>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+    clear_color.0 = theme.background;
+    if let Some(game_assets) = game_assets {
+        game_assets.color_materials(&theme, &mut materials);
+    }
+    for (mut text, theme_text) in text_query.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.color = theme_text.color(&theme);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_colors_come_from_the_default_theme() {
+        let theme: Theme = ron::de::from_str("(background: \"000000\", wall: \"FFFFFF\")").unwrap();
+        assert_eq!(theme.background, Color::hex("000000").unwrap());
+        assert_eq!(theme.wall, Color::hex("FFFFFF").unwrap());
+        assert_eq!(theme.player_two, Theme::default().player_two);
+        assert_eq!(theme.stars, Theme::default().stars);
+        assert_eq!(theme.star_density, 1.0);
+    }
+
+    #[test]
+    fn built_in_themes_load() {
+        for name in THEMES.iter() {
+            let content = fs::read_to_string(game_assets::asset_path(&format!(
+                "{}/{}.ron",
+                THEME_DIR, name
+            )))
+            .unwrap();
+            assert!(ron::de::from_str::<Theme>(&content).is_ok(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{
+    settings::Settings,
+    skins::{self, TrailStyle},
+    theme::Theme,
+    Player, PlayerIndex, Velocity, PLAY_DIAGONAL_WEIGHTED,
+};
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+const TRAIL_SEGMENTS: usize = 12;
+// opacity of the segment right behind the player
+const TRAIL_MAX_ALPHA: f32 = 0.6;
+// speed (relative to the weighted play area diagonal per second) at which the trail is complete
+const TRAIL_FULL_SPEED: f32 = 1.0;
+// the long trail skips every other position
+const TRAIL_HISTORY: usize = 2 * TRAIL_SEGMENTS + 1;
+
+// component of the player: its last positions, newest first
+pub struct Trail(VecDeque<Vec2>);
+
+// component: the player it belongs to and the index that counts from the player backwards
+pub struct TrailSegment {
+    owner: Entity,
+    index: usize,
+}
+
+// every segment has its own material so it can fade out
+pub fn spawn_trail(commands: &mut Commands, materials: &mut Assets<ColorMaterial>, player: Entity) {
+    for index in 0..TRAIL_SEGMENTS {
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: materials.add(Color::NONE.into()),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: true,
+                },
+                ..Default::default()
+            })
+            .insert(TrailSegment {
+                owner: player,
+                index,
+            });
+    }
+    commands
+        .entity(player)
+        .insert(Trail(VecDeque::with_capacity(TRAIL_HISTORY)));
+}
+
+// the trail gets longer and more opaque the faster the player moves, its style comes from the skin
+pub fn update_trail(
+    settings: Res<Settings>,
+    theme: Res<Theme>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query_set: QuerySet<(
+        Query<
+            (
+                Entity,
+                &Transform,
+                &Sprite,
+                &Velocity,
+                &PlayerIndex,
+                &mut Trail,
+            ),
+            With<Player>,
+        >,
+        Query<(
+            &mut Transform,
+            &mut Sprite,
+            &mut Visible,
+            &Handle<ColorMaterial>,
+            &TrailSegment,
+        )>,
+    )>,
+) {
+    let style = skins::selected_skin(&settings).trail;
+    let step = if style == TrailStyle::Long { 2 } else { 1 };
+    // speed factor, size, color and the positions the segments take of every player
+    let mut trails = HashMap::new();
+    for (entity, tf, sprite, velocity, player_index, mut trail) in query_set.q0_mut().iter_mut() {
+        trail.0.push_front(tf.translation.truncate());
+        trail.0.truncate(TRAIL_HISTORY);
+        let mut speed_factor =
+            (velocity.0.length() / (TRAIL_FULL_SPEED * PLAY_DIAGONAL_WEIGHTED)).min(1.0);
+        if settings.reduced_motion || style == TrailStyle::Off {
+            speed_factor = 0.0;
+        }
+        let positions: Vec<Vec2> = trail.0.iter().copied().collect();
+        // the trail has the color of its player
+        let color = theme.player_color(player_index.0);
+        trails.insert(entity, (speed_factor, sprite.size, color, positions));
+    }
+    for (mut tf, mut sprite, mut visible, material, segment) in query_set.q1_mut().iter_mut() {
+        let (speed_factor, player_size, color, positions) = match trails.get(&segment.owner) {
+            Some((speed_factor, player_size, color, positions)) => {
+                (*speed_factor, *player_size, *color, positions)
+            }
+            None => {
+                visible.is_visible = false;
+                continue;
+            }
+        };
+        let visible_segments = (TRAIL_SEGMENTS as f32 * speed_factor).ceil() as usize;
+        // the newest position is covered by the player itself
+        let position = positions.get((segment.index + 1) * step);
+        let gap = style == TrailStyle::Dots && segment.index % 2 == 1;
+        visible.is_visible = segment.index < visible_segments && position.is_some() && !gap;
+        if !visible.is_visible {
+            continue;
+        }
+        if let Some(position) = position {
+            tf.translation = position.extend(0.9);
+        }
+        let fade = 1.0 - segment.index as f32 / TRAIL_SEGMENTS as f32;
+        sprite.size = player_size * (0.5 + 0.5 * fade);
+        if style == TrailStyle::Dots {
+            sprite.size *= 0.4;
+        }
+        if let Some(material) = materials.get_mut(material) {
+            let mut color = color;
+            color.set_a(TRAIL_MAX_ALPHA * speed_factor * fade);
+            material.color = color;
+        }
+    }
+}
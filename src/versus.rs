@@ -0,0 +1,828 @@
+// head-to-head over UDP: both instances play the same seed, each one sees the other as a ghost.
+// start two instances with `--versus <local address> <peer address>`, for example
+// `--versus 127.0.0.1:7001 127.0.0.1:7002` and `--versus 127.0.0.1:7002 127.0.0.1:7001`.
+// the handshake measures the round trip and agrees on the moment of tick 0, so both sides count
+// the same ticks. the local player is moved in fixed ticks by step, and the packets carry its
+// pushes and the top of its view with their tick. the ghost is simulated with the same steps from
+// the start: up to the newest tick the other side sent it is confirmed, after that it is predicted
+// without pushes. when the next packet arrives the prediction is thrown away and the ghost rolls
+// back to the confirmed state and simulates the new pushes. the score, the crash and the attacks
+// are not simulated, every side reports its own
+use crate::{
+    game_assets::GameAssets,
+    ghost,
+    lanes::{GameMode, Lane, LaneViews},
+    menu,
+    settings::Settings,
+    theme::{Theme, ThemeText},
+    AppState, Crashed, GatePassedEvent, Player, PlayerPushedEvent, Score, Velocity, Wall,
+    WindowSizeDiagonalWeighted, PLAY_DIAGONAL_WEIGHTED, PLAY_WIDTH, VEL_CLIPPING,
+};
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::net::{SocketAddr, UdpSocket};
+
+// both players are moved in fixed steps of this many seconds
+const TICK: f32 = 1.0 / 60.0;
+// seconds between two packets
+const SEND_INTERVAL: f32 = 0.05;
+// seconds between the agreement and tick 0, the start packets are repeated until then
+const START_DELAY: f32 = 0.5;
+// the ghost is predicted at most this many ticks past the newest tick of the other side
+const MAX_PREDICTION: u32 = 60;
+// the opponent counts as disconnected after this many seconds without a packet
+const TIMEOUT: f32 = 5.0;
+const MAX_PACKET_SIZE: usize = 16 * 1024;
+// relative to the play width
+const ATTACK_WALL_WIDTH: f32 = 0.3;
+// like the player sprite in setup
+const PLAYER_HALF_SIZE: f32 = 0.015 * PLAY_DIAGONAL_WEIGHTED;
+
+// a player that is moved in fixed ticks, both start where setup spawns the player
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Body {
+    tick: u32,
+    position: Vec2,
+    velocity: Vec2,
+    // the top of the view, nothing goes above it
+    view_top: f32,
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Body {
+            tick: 0,
+            position: Vec2::new(Lane(0).x(), 0.0),
+            velocity: Vec2::ZERO,
+            view_top: f32::INFINITY,
+        }
+    }
+}
+
+// one tick of apply_velocity, friction and the clamps of player_check_leave_view
+fn step(body: &mut Body) {
+    body.tick += 1;
+    body.position += body.velocity * TICK;
+    if body.velocity.length() >= VEL_CLIPPING {
+        body.velocity *= VEL_CLIPPING.powf(TICK);
+        if body.velocity.length() < VEL_CLIPPING {
+            body.velocity = Vec2::ZERO;
+        }
+    }
+    let left = Lane(0).x() - PLAY_WIDTH / 2.0;
+    let right = Lane(0).x() + PLAY_WIDTH / 2.0;
+    if body.position.x - PLAYER_HALF_SIZE < left {
+        body.velocity = Vec2::ZERO;
+        body.position.x = left + PLAYER_HALF_SIZE;
+    } else if body.position.x + PLAYER_HALF_SIZE > right {
+        body.velocity = Vec2::ZERO;
+        body.position.x = right - PLAYER_HALF_SIZE;
+    }
+    if body.position.y + PLAYER_HALF_SIZE > body.view_top {
+        body.velocity = Vec2::ZERO;
+        body.position.y = body.view_top - PLAYER_HALF_SIZE;
+    }
+}
+
+// steps the body to the tick, the pushes and view tops of a tick are applied before its step
+fn simulate(body: &mut Body, pushes: &[(u32, (f32, f32))], view_tops: &[(u32, f32)], to_tick: u32) {
+    while body.tick < to_tick {
+        let tick = body.tick + 1;
+        for (_, impulse) in pushes.iter().filter(|(push_tick, _)| *push_tick == tick) {
+            body.velocity += Vec2::new(impulse.0, impulse.1);
+        }
+        if let Some((_, view_top)) = view_tops.iter().find(|(top_tick, _)| *top_tick == tick) {
+            body.view_top = *view_top;
+        }
+        step(body);
+    }
+}
+
+// the inputs of a side with the tick they are applied in, lost packets are made up for by the
+// next one because everything the other side hasn't confirmed is sent again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PeerState {
+    match_seed: u64,
+    // newer packets have a higher sequence, older ones are dropped
+    sequence: u32,
+    // every input up to this tick is in the packet
+    tick: u32,
+    // the newest tick of the receiver that the sender has every input of
+    ack: u32,
+    // velocity changes
+    pushes: Vec<(u32, (f32, f32))>,
+    // changes of the top of the view
+    view_tops: Vec<(u32, f32)>,
+    score: i32,
+    // walls sent to the other side
+    attacks: u32,
+    crash_tick: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Packet {
+    // sent while connecting. peer_seed is the seed of the other side once it is known, clock is the
+    // time since the sender started connecting and echo the newest clock of the other side plus the
+    // time it was held, so the other side can measure the round trip
+    Hello {
+        seed: u64,
+        peer_seed: Option<u64>,
+        attacks: bool,
+        clock: f32,
+        echo: Option<f32>,
+    },
+    // the side with the lower seed decides when tick 0 is, it is in countdown seconds on arrival
+    Start {
+        match_seed: u64,
+        countdown: f32,
+    },
+    State(PeerState),
+}
+
+// the socket and the address of the other instance
+struct Link {
+    socket: UdpSocket,
+    peer: SocketAddr,
+}
+
+// Resource: the versus mode is only available with `--versus <local address> <peer address>`
+pub struct Network {
+    link: Option<Link>,
+}
+
+impl Network {
+    pub fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let link = args
+            .iter()
+            .position(|arg| arg == "--versus")
+            .and_then(|index| match (args.get(index + 1), args.get(index + 2)) {
+                (Some(local), Some(peer)) => Some((local, peer)),
+                _ => {
+                    eprintln!("Usage: --versus <local address> <peer address>");
+                    None
+                }
+            })
+            .and_then(|(local, peer)| {
+                let link = peer
+                    .parse::<SocketAddr>()
+                    .map_err(|err| err.to_string())
+                    .and_then(|peer| {
+                        let socket = UdpSocket::bind(local).map_err(|err| err.to_string())?;
+                        socket
+                            .set_nonblocking(true)
+                            .map_err(|err| err.to_string())?;
+                        Ok(Link { socket, peer })
+                    });
+                link.map_err(|err| eprintln!("Could not start the versus mode: {}", err))
+                    .ok()
+            });
+        Network { link }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.link.is_some()
+    }
+
+    fn send(&self, packet: &Packet) {
+        let link = match self.link.as_ref() {
+            Some(link) => link,
+            None => return,
+        };
+        match serde_json::to_vec(packet) {
+            // the next packet makes up for a failed one
+            Ok(bytes) => {
+                let _ = link.socket.send_to(&bytes, link.peer);
+            }
+            Err(err) => eprintln!("Could not serialize a packet: {}", err),
+        }
+    }
+
+    // every packet that arrived from the peer since the last call
+    fn receive(&self) -> Vec<Packet> {
+        let link = match self.link.as_ref() {
+            Some(link) => link,
+            None => return Vec::new(),
+        };
+        let mut packets = Vec::new();
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        while let Ok((length, address)) = link.socket.recv_from(&mut buffer) {
+            if address != link.peer {
+                continue;
+            }
+            if let Ok(packet) = serde_json::from_slice(&buffer[..length]) {
+                packets.push(packet);
+            }
+        }
+        packets
+    }
+}
+
+// Resource: while waiting for the other instance, both sides pick a seed and play on the combination
+pub struct Handshake {
+    seed: u64,
+    peer_seed: Option<u64>,
+    peer_attacks: bool,
+    // seconds since connecting started
+    clock: f32,
+    since_sent: f32,
+    // the newest clock of the other side and when it arrived
+    peer_clock: Option<(f32, f32)>,
+    round_trip: Option<f32>,
+    // the clock at tick 0
+    start_at: Option<f32>,
+}
+
+impl Handshake {
+    fn match_seed(&self) -> Option<u64> {
+        self.peer_seed.map(|peer_seed| self.seed ^ peer_seed)
+    }
+
+    fn is_leader(&self) -> bool {
+        self.peer_seed
+            .map_or(false, |peer_seed| self.seed <= peer_seed)
+    }
+}
+
+// Resource: the running match
+pub struct VersusMatch {
+    pub seed: u64,
+    attacks_enabled: bool,
+    // seconds since tick 0, the same on both sides
+    time: f32,
+    last_sent: f32,
+    last_received: f32,
+    sequence: u32,
+    // the local player and its inputs the other side hasn't confirmed yet
+    local: Body,
+    pushes: Vec<(u32, (f32, f32))>,
+    view_tops: Vec<(u32, f32)>,
+    attacks_sent: u32,
+    crash_tick: Option<u32>,
+    // the newest state of the other side and its player at the newest tick it sent
+    remote: Option<PeerState>,
+    confirmed: Body,
+    attacks_received: u32,
+}
+
+impl VersusMatch {
+    fn new(seed: u64, attacks_enabled: bool, time: f32) -> Self {
+        VersusMatch {
+            seed,
+            attacks_enabled,
+            time,
+            last_sent: time,
+            last_received: time,
+            sequence: 0,
+            local: Body::default(),
+            pushes: Vec::new(),
+            view_tops: Vec::new(),
+            attacks_sent: 0,
+            crash_tick: None,
+            remote: None,
+            confirmed: Body::default(),
+            attacks_received: 0,
+        }
+    }
+
+    fn tick(&self) -> u32 {
+        (self.time.max(0.0) / TICK) as u32
+    }
+
+    fn disconnected(&self) -> bool {
+        self.time - self.last_received > TIMEOUT
+    }
+
+    // the opponent is done when it crashed or is gone
+    fn opponent_finished(&self) -> bool {
+        self.disconnected()
+            || self
+                .remote
+                .as_ref()
+                .map_or(false, |remote| remote.crash_tick.is_some())
+    }
+
+    fn opponent_score(&self) -> i32 {
+        self.remote.as_ref().map_or(0, |remote| remote.score)
+    }
+
+    // rolls the opponent forward with the inputs of a newer packet
+    fn receive(&mut self, state: PeerState) {
+        let confirmed_tick = self.confirmed.tick;
+        let to_tick = state
+            .crash_tick
+            .map_or(state.tick, |crash_tick| crash_tick.min(state.tick));
+        let pushes: Vec<_> = state
+            .pushes
+            .iter()
+            .copied()
+            .filter(|(tick, _)| *tick > confirmed_tick)
+            .collect();
+        let view_tops: Vec<_> = state
+            .view_tops
+            .iter()
+            .copied()
+            .filter(|(tick, _)| *tick > confirmed_tick)
+            .collect();
+        simulate(&mut self.confirmed, &pushes, &view_tops, to_tick);
+        // the other side has these now
+        self.pushes.retain(|(tick, _)| *tick > state.ack);
+        self.view_tops.retain(|(tick, _)| *tick > state.ack);
+        self.remote = Some(state);
+    }
+
+    // the confirmed opponent predicted to the current tick without further pushes
+    fn opponent_position(&self) -> Option<Vec2> {
+        let remote = self.remote.as_ref()?;
+        let mut to_tick = self.tick().min(self.confirmed.tick + MAX_PREDICTION);
+        if let Some(crash_tick) = remote.crash_tick {
+            to_tick = to_tick.min(crash_tick);
+        }
+        let mut predicted = self.confirmed;
+        simulate(&mut predicted, &[], &[], to_tick);
+        Some(predicted.position)
+    }
+}
+
+// components
+// moved by step_local_player instead of apply_velocity and friction
+pub struct FixedStep;
+pub struct Opponent;
+pub struct OpponentText;
+// on the deathscreen
+pub struct VersusResultText;
+
+pub fn spawn_connecting_screen(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    theme: Res<Theme>,
+    window_size_diag: Res<WindowSizeDiagonalWeighted>,
+    network: Res<Network>,
+) {
+    let peer = network
+        .link
+        .as_ref()
+        .map_or(String::new(), |link| link.peer.to_string());
+    menu::spawn_list_screen(
+        &mut commands,
+        &game_assets,
+        &theme,
+        window_size_diag.0,
+        "Versus",
+        vec![(format!("Waiting for {}", peer), ThemeText::Secondary)],
+    );
+    commands.insert_resource(Handshake {
+        seed: rand::thread_rng().gen::<u64>(),
+        peer_seed: None,
+        peer_attacks: false,
+        clock: 0.0,
+        since_sent: f32::INFINITY,
+        peer_clock: None,
+        round_trip: None,
+        start_at: None,
+    });
+}
+
+// exchanges the seeds and measures the round trip, then the leader picks the moment of tick 0
+// and the match starts on both sides at about the same time
+pub fn connect(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    network: Res<Network>,
+    mut handshake: ResMut<Handshake>,
+    mut mode: ResMut<GameMode>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    handshake.clock += time.delta_seconds();
+    let clock = handshake.clock;
+    let mut peer_ready = false;
+    for packet in network.receive() {
+        match packet {
+            Packet::Hello {
+                seed,
+                peer_seed,
+                attacks,
+                clock: peer_clock,
+                echo,
+            } => {
+                handshake.peer_seed = Some(seed);
+                handshake.peer_attacks = attacks;
+                handshake.peer_clock = Some((peer_clock, clock));
+                if let Some(echo) = echo {
+                    handshake.round_trip = Some(clock - echo);
+                }
+                peer_ready |= peer_seed == Some(handshake.seed);
+            }
+            Packet::Start {
+                match_seed,
+                countdown,
+            } => {
+                if handshake.start_at.is_none() && handshake.match_seed() == Some(match_seed) {
+                    handshake.start_at = Some(clock + countdown);
+                }
+            }
+            // the start packets got lost and the other side already plays
+            Packet::State(state) => {
+                if handshake.start_at.is_none() && handshake.match_seed() == Some(state.match_seed)
+                {
+                    let latency = handshake.round_trip.unwrap_or(0.0) / 2.0;
+                    handshake.start_at = Some(clock - state.tick as f32 * TICK - latency);
+                }
+            }
+        }
+    }
+    if handshake.is_leader()
+        && peer_ready
+        && handshake.round_trip.is_some()
+        && handshake.start_at.is_none()
+    {
+        handshake.start_at = Some(clock + START_DELAY);
+    }
+
+    handshake.since_sent += time.delta_seconds();
+    if handshake.since_sent >= SEND_INTERVAL {
+        handshake.since_sent = 0.0;
+        let start = handshake
+            .match_seed()
+            .zip(handshake.start_at)
+            .filter(|_| handshake.is_leader());
+        match start {
+            Some((match_seed, start_at)) => network.send(&Packet::Start {
+                match_seed,
+                // tick 0 arrives together with the packet on the other side
+                countdown: start_at - clock - handshake.round_trip.unwrap_or(0.0) / 2.0,
+            }),
+            None => network.send(&Packet::Hello {
+                seed: handshake.seed,
+                peer_seed: handshake.peer_seed,
+                attacks: settings.versus_attacks,
+                clock,
+                echo: handshake
+                    .peer_clock
+                    .map(|(peer_clock, received_at)| peer_clock + clock - received_at),
+            }),
+        }
+    }
+
+    match (handshake.match_seed(), handshake.start_at) {
+        (Some(match_seed), Some(start_at)) if clock >= start_at => {
+            commands.insert_resource(VersusMatch::new(
+                match_seed,
+                settings.versus_attacks && handshake.peer_attacks,
+                clock - start_at,
+            ));
+            *mode = GameMode::Versus;
+            app_state.set(AppState::Playing.into());
+        }
+        _ => {}
+    }
+}
+
+pub fn spawn_opponent(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    theme: Res<Theme>,
+    mode: Res<GameMode>,
+) {
+    if *mode != GameMode::Versus {
+        return;
+    }
+    let opponent = ghost::spawn_ghost_sprite(&mut commands, &game_assets);
+    commands.entity(opponent).insert(Opponent);
+    let text = ghost::spawn_hud_line(&mut commands, &game_assets, &theme);
+    commands.entity(text).insert(OpponentText);
+}
+
+// a wall without a gate somewhere just above the view
+fn spawn_attack_wall(commands: &mut Commands, game_assets: &GameAssets, lane_views: &LaneViews) {
+    let lane = Lane(0);
+    let view = lane_views.get(&lane);
+    let width = ATTACK_WALL_WIDTH * PLAY_WIDTH;
+    let x = rand::thread_rng().gen_range(-(PLAY_WIDTH - width) / 2.0..(PLAY_WIDTH - width) / 2.0);
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: game_assets.wall_material.clone(),
+            sprite: Sprite::new(Vec2::new(width, PLAY_DIAGONAL_WEIGHTED * 0.025)),
+            transform: Transform::from_xyz(
+                lane.x() + x,
+                view.camera_y + view.half_height + 0.05 * PLAY_DIAGONAL_WEIGHTED,
+                0.1,
+            ),
+            ..Default::default()
+        })
+        .insert(Wall)
+        .insert(lane);
+}
+
+// counts the time since tick 0, keeps running on the deathscreen until both are done
+pub fn advance_clock(
+    time: Res<Time>,
+    mode: Res<GameMode>,
+    versus_match: Option<ResMut<VersusMatch>>,
+) {
+    if let Some(mut versus_match) = versus_match {
+        if *mode == GameMode::Versus {
+            versus_match.time += time.delta_seconds();
+        }
+    }
+}
+
+// moves the local player in fixed ticks instead of apply_velocity and friction, so the other side
+// can simulate the same path from its pushes
+pub fn step_local_player(
+    mode: Res<GameMode>,
+    lane_views: Res<LaneViews>,
+    versus_match: Option<ResMut<VersusMatch>>,
+    mut ev_player_pushed: EventReader<PlayerPushedEvent>,
+    mut player_query: Query<(&mut Transform, &mut Velocity), (With<Player>, Without<Crashed>)>,
+) {
+    let mut versus_match = match versus_match {
+        Some(versus_match) if *mode == GameMode::Versus => versus_match,
+        _ => return,
+    };
+    let (mut tf, mut velocity) = match player_query.single_mut() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    // gesture_on_player already added them to the velocity, the other side adds them before the
+    // next step
+    let next_tick = versus_match.local.tick + 1;
+    for ev in ev_player_pushed.iter() {
+        versus_match
+            .pushes
+            .push((next_tick, (ev.impulse.x, ev.impulse.y)));
+    }
+    let view = lane_views.get(&Lane(0));
+    let view_top = view.camera_y + view.half_height;
+    let mut body = versus_match.local;
+    body.velocity = velocity.0.truncate();
+    let to_tick = versus_match.tick();
+    while body.tick < to_tick {
+        if body.view_top != view_top {
+            body.view_top = view_top;
+            versus_match.view_tops.push((body.tick + 1, view_top));
+        }
+        step(&mut body);
+    }
+    versus_match.local = body;
+    tf.translation.x = body.position.x;
+    tf.translation.y = body.position.y;
+    velocity.0 = body.velocity.extend(0.0);
+}
+
+// sends the inputs of the local player and receives the opponent, keeps running on the deathscreen
+// until both are done
+pub fn sync(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    network: Res<Network>,
+    game_assets: Res<GameAssets>,
+    lane_views: Res<LaneViews>,
+    versus_match: Option<ResMut<VersusMatch>>,
+    mut ev_gate_passed: EventReader<GatePassedEvent>,
+    player_query: Query<(&Score, Option<&Crashed>), With<Player>>,
+) {
+    let mut versus_match = match versus_match {
+        Some(versus_match) if *mode == GameMode::Versus => versus_match,
+        _ => return,
+    };
+
+    // the local player
+    if versus_match.attacks_enabled {
+        versus_match.attacks_sent += ev_gate_passed.iter().count() as u32;
+    }
+    let (score, crashed) = match player_query.single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    // a crashed player doesn't move anymore
+    if crashed.is_some() && versus_match.crash_tick.is_none() {
+        versus_match.crash_tick = Some(versus_match.local.tick);
+    }
+    if versus_match.time - versus_match.last_sent >= SEND_INTERVAL {
+        versus_match.last_sent = versus_match.time;
+        versus_match.sequence += 1;
+        network.send(&Packet::State(PeerState {
+            match_seed: versus_match.seed,
+            sequence: versus_match.sequence,
+            tick: versus_match.local.tick,
+            ack: versus_match.confirmed.tick,
+            pushes: versus_match.pushes.clone(),
+            view_tops: versus_match.view_tops.clone(),
+            score: score.0,
+            attacks: versus_match.attacks_sent,
+            crash_tick: versus_match.crash_tick,
+        }));
+    }
+
+    // the opponent
+    for packet in network.receive() {
+        if let Packet::State(state) = packet {
+            if state.match_seed != versus_match.seed {
+                continue;
+            }
+            versus_match.last_received = versus_match.time;
+            let newer = versus_match
+                .remote
+                .as_ref()
+                .map_or(true, |remote| state.sequence > remote.sequence);
+            if newer {
+                versus_match.receive(state);
+            }
+        }
+    }
+    let attacks = versus_match
+        .remote
+        .as_ref()
+        .map_or(0, |remote| remote.attacks);
+    while versus_match.attacks_received < attacks {
+        versus_match.attacks_received += 1;
+        if crashed.is_none() {
+            spawn_attack_wall(&mut commands, &game_assets, &lane_views);
+        }
+    }
+}
+
+// the ghost of the opponent and its score below the scoreboard
+pub fn update_opponent(
+    versus_match: Option<Res<VersusMatch>>,
+    lane_views: Res<LaneViews>,
+    player_query: Query<&Score, With<Player>>,
+    mut opponent_query: Query<(&mut Transform, &mut Visible), With<Opponent>>,
+    mut text_query: Query<(&mut Transform, &mut Text), (With<OpponentText>, Without<Opponent>)>,
+) {
+    let versus_match = match versus_match {
+        Some(versus_match) => versus_match,
+        None => return,
+    };
+    let position = versus_match.opponent_position();
+    for (mut tf, mut visible) in opponent_query.iter_mut() {
+        visible.is_visible = position.is_some();
+        if let Some(position) = position {
+            tf.translation.x = position.x;
+            tf.translation.y = position.y;
+        }
+    }
+    let score = player_query.single().map_or(0, |score| score.0);
+    let status = if versus_match.disconnected() {
+        " (gone)"
+    } else if versus_match.opponent_finished() {
+        " (crashed)"
+    } else {
+        ""
+    };
+    for (mut tf, mut text) in text_query.iter_mut() {
+        if let Some(section) = text.sections.get_mut(0) {
+            section.value = format!(
+                "Opponent {}{} ({:+})",
+                versus_match.opponent_score(),
+                status,
+                score - versus_match.opponent_score()
+            );
+        }
+        tf.translation.y = ghost::hud_line_y(&lane_views);
+    }
+}
+
+// the result is known once the opponent is done too
+pub fn update_result_text(
+    versus_match: Option<Res<VersusMatch>>,
+    player_query: Query<&Score, With<Player>>,
+    mut query: Query<&mut Text, With<VersusResultText>>,
+) {
+    let versus_match = match versus_match {
+        Some(versus_match) => versus_match,
+        None => return,
+    };
+    let score = player_query.single().map_or(0, |score| score.0);
+    let opponent_score = versus_match.opponent_score();
+    let value = if versus_match.disconnected() {
+        format!("Opponent disconnected at {}", opponent_score)
+    } else if !versus_match.opponent_finished() {
+        format!("Opponent still playing, score {}", opponent_score)
+    } else {
+        let result = match score.cmp(&opponent_score) {
+            std::cmp::Ordering::Greater => "You win!",
+            std::cmp::Ordering::Less => "You lose!",
+            std::cmp::Ordering::Equal => "Draw!",
+        };
+        format!("{} You {} - {} Opponent", result, score, opponent_score)
+    };
+    for mut text in query.iter_mut() {
+        if let Some(section) = text.sections.get_mut(0) {
+            if section.value != value {
+                section.value = value.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moving(velocity: Vec2) -> Body {
+        Body {
+            velocity,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn simulate_integrates_velocity_and_friction() {
+        let mut body = moving(Vec2::new(30.0, 200.0));
+        simulate(&mut body, &[], &[], 90);
+
+        let mut position = Vec2::ZERO;
+        let mut velocity = Vec2::new(30.0, 200.0);
+        for _ in 0..90 {
+            position += velocity * TICK;
+            velocity *= VEL_CLIPPING.powf(TICK);
+        }
+        assert_eq!(body.tick, 90);
+        assert!(body.position.distance(position) < 1e-3);
+        assert!(body.velocity.distance(velocity) < 1e-3);
+        // friction stops the player after about -ln(VEL_CLIPPING) / v
+        let travelled = 200.0 / -VEL_CLIPPING.ln();
+        assert!((body.position.y - travelled).abs() < 0.1 * travelled);
+    }
+
+    #[test]
+    fn simulate_stops_at_the_top_of_the_view_and_the_sides() {
+        let mut body = moving(Vec2::new(0.0, 5000.0));
+        simulate(&mut body, &[], &[(1, 100.0)], 30);
+        assert_eq!(body.position.y, 100.0 - PLAYER_HALF_SIZE);
+        assert_eq!(body.velocity, Vec2::ZERO);
+
+        let mut body = moving(Vec2::new(-5000.0, 0.0));
+        simulate(&mut body, &[], &[], 30);
+        assert_eq!(
+            body.position.x,
+            Lane(0).x() - PLAY_WIDTH / 2.0 + PLAYER_HALF_SIZE
+        );
+        assert_eq!(body.velocity, Vec2::ZERO);
+    }
+
+    #[test]
+    fn pushes_replay_the_local_steps_exactly() {
+        let pushes = [(3, (40.0, 300.0)), (20, (-120.0, 80.0)), (21, (10.0, -5.0))];
+        let view_tops = [(1, 400.0), (25, 410.0)];
+
+        // like step_local_player, the push is in the velocity before the step of its tick
+        let mut local = Body::default();
+        while local.tick < 60 {
+            let tick = local.tick + 1;
+            for (_, impulse) in pushes.iter().filter(|(push_tick, _)| *push_tick == tick) {
+                local.velocity += Vec2::new(impulse.0, impulse.1);
+            }
+            if let Some((_, top)) = view_tops.iter().find(|(top_tick, _)| *top_tick == tick) {
+                local.view_top = *top;
+            }
+            step(&mut local);
+        }
+
+        let mut remote = Body::default();
+        simulate(&mut remote, &pushes, &view_tops, 60);
+        assert_eq!(local, remote);
+    }
+
+    #[test]
+    fn rolling_back_to_the_confirmed_state_gives_the_same_path() {
+        let pushes = [(5, (0.0, 250.0)), (40, (60.0, 0.0))];
+        let mut whole = Body::default();
+        simulate(&mut whole, &pushes, &[], 80);
+
+        // the first packet only knows the ticks up to 30, the prediction is discarded later
+        let mut confirmed = Body::default();
+        simulate(&mut confirmed, &pushes[..1], &[], 30);
+        let mut predicted = confirmed;
+        simulate(&mut predicted, &[], &[], 80);
+        simulate(&mut confirmed, &pushes[1..], &[], 80);
+        assert_eq!(confirmed, whole);
+        assert_ne!(predicted, whole);
+    }
+
+    #[test]
+    fn received_inputs_are_confirmed_and_acknowledged() {
+        let mut versus_match = VersusMatch::new(1, true, 1.0);
+        versus_match.pushes = vec![(10, (1.0, 2.0)), (50, (3.0, 4.0))];
+        versus_match.receive(PeerState {
+            match_seed: 1,
+            sequence: 1,
+            tick: 40,
+            ack: 30,
+            pushes: vec![(12, (0.0, 100.0)), (45, (0.0, 100.0))],
+            view_tops: vec![(1, 380.0)],
+            score: 3,
+            attacks: 0,
+            crash_tick: None,
+        });
+        assert_eq!(versus_match.confirmed.tick, 40);
+        assert_eq!(versus_match.pushes, vec![(50, (3.0, 4.0))]);
+        let mut expected = Body::default();
+        simulate(&mut expected, &[(12, (0.0, 100.0))], &[(1, 380.0)], 40);
+        assert_eq!(versus_match.confirmed, expected);
+    }
+}